@@ -57,6 +57,19 @@ type ModListItem = {
 	repo: "curseforge" | "modrinth",
 	channel: "alpha" | "beta" | "release"
 }
+/** Written next to each jar as `{jar}.source.toml`, so the server can tell packwiz where the mod came from */
+type ModSource = {
+	repo: "curseforge" | "modrinth",
+	project_id: string,
+	version_id: string
+}
+function writeModSource(modDir: string, modFileName: string, modSource: ModSource | null): Promise<void> {
+	if (modSource == null) {
+		console.warn("Couldn't figure out where " + modFileName + " came from, packwiz won't be able to update it");
+		return Promise.resolve();
+	}
+	return fs.writeFile(path.resolve(modDir, modFileName + ".source.toml"), TOML.stringify(modSource));
+}
 async function readModConfig(filePath: string) {
 	filePath = path.resolve(filePath);
 	const maybeModConfig = TOML.parse((await fs.readFile(filePath)).toString("utf8"));
@@ -157,6 +170,18 @@ for (let i = 0; i < modListConfig.mod_list.length; i += 1) {
 				await page.waitForSelector(downloadSelector);
 				if (await page.$(latestDownloadSelector)) {
 					const modName = await page.$eval(".project-header > .name-container > h1", (el) => el.innerText);
+					const modFileId = (await page.$eval(latestDownloadSelector, (el) => el.getAttribute("href") ?? ""))
+						.match(/\/download\/(\d+)/)?.[1];
+					// The page's Next.js data has the project in it, unlike the download links which only have the slug
+					const modProjectId = await page.$eval("script#__NEXT_DATA__", (el) => {
+						const projectId = JSON.parse(el.textContent ?? "{}")?.props?.pageProps?.project?.id;
+						return typeof projectId == "number" ? projectId + "" : undefined;
+					}).catch(() => undefined);
+					const modSource: ModSource | null = modFileId && modProjectId ? {
+						repo: "curseforge",
+						project_id: modProjectId,
+						version_id: modFileId
+					} : null;
 					console.info("Mod name:", modName);
 					console.info("Mod realm: unknown - assuming both");
 					await page.click(`.kebab-menu button:is(.file-row:has(.channel-tag.${mod.channel}) button)`);
@@ -175,7 +200,8 @@ for (let i = 0; i < modListConfig.mod_list.length; i += 1) {
 						fs.writeFile(
 							path.resolve(sharedModDownloadDir, downloadFileName + ".name.txt"),
 							modName + "\n"
-						)
+						),
+						writeModSource(sharedModDownloadDir, downloadFileName, modSource)
 					]);
 					break;
 				}
@@ -196,6 +222,14 @@ for (let i = 0; i < modListConfig.mod_list.length; i += 1) {
 			const downloadSelector = "a[href^=\"https://cdn.modrinth.com/data/\"][aria-label=\"Download\"]";
 			await page.waitForSelector(downloadSelector);
 			const modName = await page.$eval("h1", (el) => el.innerText);
+			// Download links look like https://cdn.modrinth.com/data/{project id}/versions/{version id}/{file name}
+			const modDownloadIds = (await page.$eval(downloadSelector, (el) => el.getAttribute("href") ?? ""))
+				.match(/\/data\/([^/]+)\/versions\/([^/]+)\//);
+			const modSource: ModSource | null = modDownloadIds ? {
+				repo: "modrinth",
+				project_id: modDownloadIds[1],
+				version_id: modDownloadIds[2]
+			} : null;
 			const modRealm = await page.$eval("section:last-child > h3 + div.tag-list", el => {
 				const text = el.innerText.toLowerCase();
 				if (text.includes("client and server")) {
@@ -229,7 +263,8 @@ for (let i = 0; i < modListConfig.mod_list.length; i += 1) {
 						fs.writeFile(
 							path.resolve(serverModDownloadDir, downloadFileName + ".name.txt"),
 							modName + "\n"
-						)
+						),
+						writeModSource(serverModDownloadDir, downloadFileName, modSource)
 					]);
 					//
 					break;
@@ -242,7 +277,8 @@ for (let i = 0; i < modListConfig.mod_list.length; i += 1) {
 						fs.writeFile(
 							path.resolve(clientModDownloadDir, downloadFileName + ".name.txt"),
 							modName + "\n"
-						)
+						),
+						writeModSource(clientModDownloadDir, downloadFileName, modSource)
 					]);
 					//
 					break;
@@ -255,7 +291,8 @@ for (let i = 0; i < modListConfig.mod_list.length; i += 1) {
 						fs.writeFile(
 							path.resolve(sharedModDownloadDir, downloadFileName + ".name.txt"),
 							modName + "\n"
-						)
+						),
+						writeModSource(sharedModDownloadDir, downloadFileName, modSource)
 					]);
 				//
 			}
@@ -273,13 +310,13 @@ const serverModDir = path.resolve(serverModDirArg)
 await fs.rm(serverModDir, { recursive: true, force: true });
 await fs.mkdir(serverModDir);
 for (const modFile of await fs.readdir(serverModDownloadDir)) {
-	if (modFile.endsWith(".txt")) {
+	if (!modFile.endsWith(".jar")) {
 		continue;
 	}
 	await fs.symlink(path.resolve(serverModDownloadDir, modFile), path.resolve(serverModDir, modFile));
 }
 for (const modFile of await fs.readdir(sharedModDownloadDir)) {
-	if (modFile.endsWith(".txt")) {
+	if (!modFile.endsWith(".jar")) {
 		continue;
 	}
 	await fs.symlink(path.resolve(sharedModDownloadDir, modFile), path.resolve(serverModDir, modFile));
@@ -0,0 +1,212 @@
+use std::{
+	collections::HashSet,
+	path::{Path, PathBuf},
+};
+
+use axum::{
+	body::Body,
+	http::{header, HeaderMap, StatusCode},
+};
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::{fs, io::AsyncWriteExt};
+use zip::ZipArchive;
+
+use crate::{
+	pack::Pack,
+	packwiz_cache::{update_packwiz_cache, JAR_SIDECAR_SUFFIXES},
+	safe_path::{check_jar_file_name, remove_file_if_exists, resolve_safe_path},
+	schemas::PackwizModSide,
+	sync::sidecar_path,
+};
+
+/// The biggest jar the admin endpoints accept
+pub const MAX_JAR_UPLOAD_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdminError {
+	#[error("admin endpoints are disabled since no admin token was set")]
+	Disabled,
+	#[error("missing or wrong admin token")]
+	Unauthorized,
+	#[error("{0} isn't a valid jar: {1}")]
+	InvalidJar(String, String),
+	#[error("{0} is already in {1}/")]
+	AlreadyExists(String, PackwizModSide),
+	#[error("{0} is bigger than {MAX_JAR_UPLOAD_SIZE} bytes")]
+	TooLarge(String),
+}
+impl AdminError {
+	pub fn status_code(&self) -> StatusCode {
+		match self {
+			AdminError::Disabled => StatusCode::NOT_FOUND,
+			AdminError::Unauthorized => StatusCode::UNAUTHORIZED,
+			AdminError::InvalidJar(..) => StatusCode::BAD_REQUEST,
+			AdminError::AlreadyExists(..) => StatusCode::CONFLICT,
+			AdminError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+		}
+	}
+}
+
+/// A jar in the download dir, as the admin endpoints describe it after changing it
+#[derive(Debug, Serialize)]
+pub struct AdminJar {
+	pub realm: PackwizModSide,
+	pub file_name: String,
+	/// File size in bytes
+	pub size: u64,
+	pub sha512: String,
+}
+
+/// Makes sure the request has an `Authorization: Bearer {token}` header with the pack's admin token
+pub fn check_admin_token(pack: &Pack, request_headers: &HeaderMap) -> Result<(), AdminError> {
+	let admin_token = pack.admin_token.as_deref().ok_or(AdminError::Disabled)?;
+	let request_token = request_headers
+		.get(header::AUTHORIZATION)
+		.and_then(|authorization| authorization.to_str().ok()?.strip_prefix("Bearer "))
+		.ok_or(AdminError::Unauthorized)?;
+	// Every byte gets compared so the time it takes doesn't give away how much of the token was right
+	let differences = admin_token
+		.bytes()
+		.zip(request_token.bytes())
+		.fold(0, |differences, (a, b)| differences | (a ^ b));
+	if differences != 0 || admin_token.len() != request_token.len() {
+		return Err(AdminError::Unauthorized);
+	}
+	Ok(())
+}
+
+/// Returns the other realm which already has a jar with this name, if there is one
+async fn find_other_realm(
+	pack: &Pack,
+	realm: PackwizModSide,
+	jar_file_name: &str,
+) -> anyhow::Result<Option<PackwizModSide>> {
+	for other_realm in PackwizModSide::all().filter(|other_realm| *other_realm != realm) {
+		if fs::try_exists(pack.download_dir.join(other_realm.to_string()).join(jar_file_name)).await? {
+			return Ok(Some(other_realm));
+		}
+	}
+	Ok(None)
+}
+
+/// Re-hashes the jar and updates the packwiz files straight away, rather than waiting for the file watcher
+async fn refresh_jars(pack: &Pack, changed_paths: &[PathBuf]) -> anyhow::Result<()> {
+	for changed_path in changed_paths {
+		pack.file_info.forget(changed_path);
+	}
+	update_packwiz_cache(pack, changed_paths.iter().cloned().collect::<HashSet<_>>()).await
+}
+
+async fn admin_jar(pack: &Pack, realm: PackwizModSide, jar_path: &Path) -> anyhow::Result<AdminJar> {
+	let jar_info = pack.file_info.get_info_from_file(jar_path).await?;
+	Ok(AdminJar {
+		realm,
+		file_name: jar_path.file_name().unwrap_or_default().to_string_lossy().into(),
+		size: jar_info.size,
+		sha512: jar_info.sha512.to_string(),
+	})
+}
+
+/// Writes the body to the file as it arrives, so no more than a chunk of it is ever in memory
+async fn write_upload(jar_file_name: &str, body: Body, path: &Path) -> anyhow::Result<()> {
+	let mut file = fs::File::create(path).await?;
+	let mut body_stream = body.into_data_stream();
+	let mut size = 0;
+	while let Some(chunk) = body_stream.next().await {
+		let chunk = chunk?;
+		size += chunk.len();
+		if size > MAX_JAR_UPLOAD_SIZE {
+			return Err(AdminError::TooLarge(jar_file_name.into()).into());
+		}
+		file.write_all(&chunk).await?;
+	}
+	file.flush().await?;
+	let path = path.to_owned();
+	tokio::task::spawn_blocking(move || ZipArchive::new(std::fs::File::open(path)?).map(drop))
+		.await?
+		.map_err(|err| AdminError::InvalidJar(jar_file_name.into(), err.to_string()))?;
+	Ok(())
+}
+
+/// Adds a jar to a realm or replaces the one which is already there. A name for the mod can be given, otherwise the
+/// jar's fabric.mod.json decides it. The replaced jar's name, option and source files are removed, since they
+/// described a different jar.
+pub async fn upload_jar(
+	pack: &Pack,
+	realm: PackwizModSide,
+	jar_file_name: &str,
+	mod_name: Option<&str>,
+	body: Body,
+) -> anyhow::Result<AdminJar> {
+	check_jar_file_name(jar_file_name)?;
+	if let Some(other_realm) = find_other_realm(pack, realm, jar_file_name).await? {
+		return Err(AdminError::AlreadyExists(jar_file_name.into(), other_realm).into());
+	}
+
+	let jar_path = pack.download_dir.join(realm.to_string()).join(jar_file_name);
+	// Written next to the jar then renamed, so the file watcher never sees half a jar
+	let temp_path = jar_path.with_file_name(format!(".{jar_file_name}.upload"));
+	if let Err(err) = write_upload(jar_file_name, body, &temp_path).await {
+		remove_file_if_exists(&temp_path).await?;
+		return Err(err);
+	}
+	fs::rename(&temp_path, &jar_path).await?;
+	let mut changed_paths = vec![jar_path.clone()];
+	for suffix in JAR_SIDECAR_SUFFIXES {
+		let path = sidecar_path(&jar_path, suffix);
+		match mod_name {
+			Some(mod_name) if suffix == ".name.txt" => fs::write(&path, format!("{}\n", mod_name.trim())).await?,
+			_ => remove_file_if_exists(&path).await?,
+		}
+		changed_paths.push(path);
+	}
+	refresh_jars(pack, &changed_paths).await?;
+	admin_jar(pack, realm, &jar_path).await
+}
+
+/// Moves a jar and the files next to it into another realm
+pub async fn move_jar(
+	pack: &Pack,
+	realm: PackwizModSide,
+	jar_file_name: &str,
+	new_realm: PackwizModSide,
+) -> anyhow::Result<AdminJar> {
+	check_jar_file_name(jar_file_name)?;
+	let realm_dir = pack.download_dir.join(realm.to_string());
+	resolve_safe_path(&realm_dir, jar_file_name).await?;
+	let jar_path = realm_dir.join(jar_file_name);
+	let new_jar_path = pack.download_dir.join(new_realm.to_string()).join(jar_file_name);
+	if realm == new_realm {
+		return admin_jar(pack, realm, &jar_path).await;
+	}
+	if fs::try_exists(&new_jar_path).await? {
+		return Err(AdminError::AlreadyExists(jar_file_name.into(), new_realm).into());
+	}
+	let mut changed_paths = Vec::with_capacity(2);
+	for suffix in [""].into_iter().chain(JAR_SIDECAR_SUFFIXES) {
+		let (path, new_path) = (sidecar_path(&jar_path, suffix), sidecar_path(&new_jar_path, suffix));
+		if suffix.is_empty() || fs::try_exists(&path).await? {
+			fs::rename(&path, &new_path).await?;
+			changed_paths.extend([path, new_path]);
+		}
+	}
+	refresh_jars(pack, &changed_paths).await?;
+	admin_jar(pack, new_realm, &new_jar_path).await
+}
+
+/// Deletes a jar and the files next to it
+pub async fn delete_jar(pack: &Pack, realm: PackwizModSide, jar_file_name: &str) -> anyhow::Result<()> {
+	check_jar_file_name(jar_file_name)?;
+	let realm_dir = pack.download_dir.join(realm.to_string());
+	resolve_safe_path(&realm_dir, jar_file_name).await?;
+	let jar_path = realm_dir.join(jar_file_name);
+	let mut changed_paths = vec![jar_path.clone()];
+	fs::remove_file(&jar_path).await?;
+	for suffix in JAR_SIDECAR_SUFFIXES {
+		let path = sidecar_path(&jar_path, suffix);
+		remove_file_if_exists(&path).await?;
+		changed_paths.push(path);
+	}
+	refresh_jars(pack, &changed_paths).await
+}
@@ -0,0 +1,166 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	exports::{ExportedCopyFile, ExportedFiles, ExportedMod},
+	pack::Pack,
+	schemas::{DrakermoreModConfig, MinecraftClientServerListInfo, ModLoader, ModRepo, ModSource, PackwizModSide},
+};
+
+/// Everything in the pack, as `/api/pack` describes it
+#[derive(Debug, Serialize)]
+pub struct ApiPack {
+	pub name: String,
+	pub pack_author: String,
+	pub pack_version: String,
+	pub minecraft_version: String,
+	pub loader: ModLoader,
+	pub loader_version: String,
+	pub minecraft_servers: Vec<MinecraftClientServerListInfo>,
+	pub urls: ApiPackUrls,
+	pub mods: Vec<ApiMod>,
+	pub copy_files: Vec<ApiCopyFile>,
+}
+
+/// Where each format of the pack can be downloaded from
+#[derive(Debug, Serialize)]
+pub struct ApiPackUrls {
+	pub packwiz: String,
+	pub mmc_pack: String,
+	pub mrpack: String,
+	pub curseforge: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiMod {
+	pub name: String,
+	pub file_name: String,
+	/// The fabric mod id, if the jar has a fabric.mod.json
+	pub mod_id: Option<String>,
+	pub version: Option<String>,
+	/// Author names from the jar's fabric.mod.json
+	pub authors: Vec<String>,
+	/// Links from the jar's fabric.mod.json, like `homepage`, `sources` and `issues`
+	pub contact: BTreeMap<String, String>,
+	pub side: PackwizModSide,
+	pub optional: bool,
+	/// File size in bytes
+	pub size: u64,
+	pub sha512: String,
+	/// Where the jar was downloaded from, if we know
+	pub source: Option<ModSource>,
+	pub download_url: String,
+}
+impl From<&ExportedMod> for ApiMod {
+	fn from(exported_mod: &ExportedMod) -> Self {
+		let fabric_mod = exported_mod.info.fabric_mod.as_ref();
+		Self {
+			name: exported_mod.name.clone(),
+			file_name: exported_mod.jar_file_name.clone(),
+			mod_id: fabric_mod.map(|fabric_mod| fabric_mod.id.clone()),
+			version: fabric_mod.map(|fabric_mod| fabric_mod.version.clone()),
+			authors: fabric_mod
+				.into_iter()
+				.flat_map(|fabric_mod| fabric_mod.authors.iter().map(|author| author.name().to_string()))
+				.collect(),
+			contact: fabric_mod
+				.map(|fabric_mod| fabric_mod.contact.clone())
+				.unwrap_or_default(),
+			side: exported_mod.side,
+			optional: exported_mod.optional,
+			size: exported_mod.info.size,
+			sha512: exported_mod.info.sha512.to_string(),
+			source: exported_mod.source.clone(),
+			download_url: exported_mod.url.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiCopyFile {
+	/// Where the file ends up in the instance
+	pub path: PathBuf,
+	pub side: PackwizModSide,
+	/// File size in bytes
+	pub size: u64,
+	pub sha512: String,
+	pub download_url: String,
+}
+impl From<&ExportedCopyFile> for ApiCopyFile {
+	fn from(copy_file: &ExportedCopyFile) -> Self {
+		Self {
+			path: copy_file.install_path.clone(),
+			side: copy_file.side,
+			size: copy_file.info.size,
+			sha512: copy_file.info.sha512.to_string(),
+			download_url: copy_file.url.clone(),
+		}
+	}
+}
+
+/// Query parameters `/api/mods` and `/api/pack` accept, every one which is set has to match
+#[derive(Debug, Default, Deserialize)]
+pub struct ApiModFilter {
+	/// Case-insensitive search through the name, file name and mod id
+	pub search: Option<String>,
+	/// Mods which get installed on the side, `both` only matches mods which get installed on both sides
+	pub side: Option<PackwizModSide>,
+	pub repo: Option<ModRepo>,
+	pub optional: Option<bool>,
+}
+impl ApiModFilter {
+	pub fn matches(&self, api_mod: &ApiMod) -> bool {
+		if let Some(search) = &self.search {
+			let search = search.to_lowercase();
+			if ![Some(&api_mod.name), Some(&api_mod.file_name), api_mod.mod_id.as_ref()]
+				.into_iter()
+				.flatten()
+				.any(|text| text.to_lowercase().contains(&search))
+			{
+				return false;
+			}
+		}
+		self.side
+			.is_none_or(|side| api_mod.side.intersection(side) == Some(side))
+			&& self
+				.repo
+				.is_none_or(|repo| api_mod.source.as_ref().is_some_and(|source| source.repo == repo))
+			&& self.optional.is_none_or(|optional| api_mod.optional == optional)
+	}
+}
+
+/// Built from the same files the packwiz index lists, so the API never disagrees with the pack
+pub fn api_pack(
+	pack: &Pack,
+	modpack: DrakermoreModConfig,
+	exported_files: &ExportedFiles,
+	filter: &ApiModFilter,
+) -> ApiPack {
+	ApiPack {
+		name: modpack.name,
+		pack_author: modpack.pack_author,
+		pack_version: modpack.pack_version,
+		minecraft_version: modpack.minecraft_version,
+		loader: modpack.loader,
+		loader_version: modpack.loader_version,
+		minecraft_servers: modpack.minecraft_servers,
+		urls: ApiPackUrls {
+			packwiz: format!("{}/packwiz/pack.toml", pack.url_prefix),
+			mmc_pack: format!("{}/mmc_pack.zip", pack.url_prefix),
+			mrpack: format!("{}/modpack.mrpack", pack.url_prefix),
+			curseforge: format!("{}/modpack.curseforge.zip", pack.url_prefix),
+		},
+		mods: api_mods(exported_files, filter),
+		copy_files: exported_files.copy_files.iter().map(ApiCopyFile::from).collect(),
+	}
+}
+
+pub fn api_mods(exported_files: &ExportedFiles, filter: &ApiModFilter) -> Vec<ApiMod> {
+	exported_files
+		.mods
+		.iter()
+		.map(ApiMod::from)
+		.filter(|api_mod| filter.matches(api_mod))
+		.collect()
+}
@@ -1,38 +1,123 @@
 use std::{
 	collections::HashMap,
 	path::{Path, PathBuf},
-	sync::{Arc, LazyLock, RwLock},
+	sync::{Arc, RwLock},
 	time::SystemTime,
 };
 
+use sha1::Sha1;
 use sha2::{Digest, Sha512};
 use tokio::fs;
 
-static CACHED_HASHES: LazyLock<RwLock<HashMap<PathBuf, (SystemTime, Arc<str>)>>> =
-	LazyLock::new(|| RwLock::new(HashMap::new()));
-
-/// Returns a hex-encoded sha512 hash from the given file, and caches the hash
-pub async fn get_hash_from_file(file_path: &Path) -> Result<Arc<str>, anyhow::Error> {
-	let file_mtime = fs::metadata(file_path).await?.modified()?;
-	if let Some(cached_hash) = CACHED_HASHES
-		.read()
-		.unwrap()
-		.get(file_path)
-		.and_then(|(cached_mtime, cached_hash)| {
-			if file_mtime > *cached_mtime {
-				None
-			} else {
-				Some(cached_hash)
-			}
-		}) {
-		return Ok(cached_hash.clone());
+use crate::fabric_mod::{read_fabric_mod_or_warn, FabricModJson};
+
+type CachedFileInfoMap = HashMap<PathBuf, (SystemTime, Arc<CachedFileInfo>)>;
+
+/// Each pack gets its own cache so packs served from the same process never see each other's entries
+#[derive(Debug, Default)]
+pub struct FileInfoCache {
+	cached_file_info: RwLock<CachedFileInfoMap>,
+}
+
+#[derive(Debug)]
+pub struct CachedFileInfo {
+	/// hex-encoded sha512 hash of the file
+	pub sha512: Arc<str>,
+	/// hex-encoded sha1 hash of the file, since some pack formats still want it
+	pub sha1: Arc<str>,
+	/// File size in bytes
+	pub size: u64,
+	/// The contents of `fabric.mod.json` if the file is a jar which has one
+	pub fabric_mod: Option<FabricModJson>,
+}
+
+impl FileInfoCache {
+	/// Returns a hex-encoded sha512 hash from the given file, and caches the hash
+	pub async fn get_hash_from_file(&self, file_path: &Path) -> Result<Arc<str>, anyhow::Error> {
+		Ok(self.get_info_from_file(file_path).await?.sha512.clone())
+	}
+
+	/// Returns the hash and mod metadata from the given file, and caches the result
+	pub async fn get_info_from_file(&self, file_path: &Path) -> Result<Arc<CachedFileInfo>, anyhow::Error> {
+		let file_mtime = fs::metadata(file_path).await?.modified()?;
+		if let Some(cached_info) =
+			self.cached_file_info
+				.read()
+				.unwrap()
+				.get(file_path)
+				.and_then(|(cached_mtime, cached_info)| {
+					if file_mtime > *cached_mtime {
+						None
+					} else {
+						Some(cached_info)
+					}
+				}) {
+			return Ok(cached_info.clone());
+		}
+		let file_bytes = fs::read(file_path).await?;
+		let fabric_mod = if file_path.extension().is_some_and(|ext| ext == "jar") {
+			read_fabric_mod_or_warn(file_path, &file_bytes)
+		} else {
+			None
+		};
+		if let Some(fabric_mod) = &fabric_mod {
+			tracing::debug!("{}: {fabric_mod}", file_path.display());
+		}
+		let info = Arc::new(CachedFileInfo {
+			sha512: hex::encode(Sha512::digest(&file_bytes)).into(),
+			sha1: hex::encode(Sha1::digest(&file_bytes)).into(),
+			size: file_bytes.len() as u64,
+			fabric_mod,
+		});
+		self.cached_file_info
+			.write()
+			.unwrap()
+			.insert(file_path.into(), (file_mtime, info.clone()));
+
+		Ok(info)
+	}
+
+	/// Removes a file from the cache, used when the file watcher sees it being deleted
+	pub fn forget(&self, file_path: &Path) {
+		self.cached_file_info.write().unwrap().remove(file_path);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{fabric_mod::FabricModEnvironment, test_utils::write_jar};
+
+	#[tokio::test]
+	async fn jar_metadata_comes_from_fabric_mod_json() {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let jar_path = temp_dir.path().join("sodium.jar");
+		write_jar(
+			&jar_path,
+			r#"{
+				"schemaVersion": 1,
+				"id": "sodium",
+				"version": "0.5.8",
+				"name": "Sodium",
+				"environment": "client",
+				"authors": ["JellySquid", { "name": "IMS", "contact": { "homepage": "https://ims.example" } }],
+				"contact": { "sources": "https://github.com/CaffeineMC/sodium-fabric" }
+			}"#,
+		);
+		let file_info = FileInfoCache::default();
+		let info = file_info.get_info_from_file(&jar_path).await.unwrap();
+		let fabric_mod = info.fabric_mod.as_ref().unwrap();
+		assert_eq!(fabric_mod.environment, FabricModEnvironment::Client);
+		assert_eq!(
+			fabric_mod.to_string(),
+			"Sodium 0.5.8 by JellySquid, IMS (https://github.com/CaffeineMC/sodium-fabric)"
+		);
+
+		// A broken fabric.mod.json doesn't stop the jar from being hashed, it just has no metadata
+		let broken_jar_path = temp_dir.path().join("broken.jar");
+		write_jar(&broken_jar_path, r#"{ "id": "broken", "#);
+		let info = file_info.get_info_from_file(&broken_jar_path).await.unwrap();
+		assert!(info.fabric_mod.is_none());
+		assert_eq!(info.size, std::fs::metadata(&broken_jar_path).unwrap().len());
 	}
-	let hash: Arc<str> = hex::encode(Sha512::digest(fs::read(file_path).await?)).into();
-	// Yes, we currently don't watch for files being deleted which could cause memory leaks, too bad!
-	CACHED_HASHES
-		.write()
-		.unwrap()
-		.insert(file_path.into(), (file_mtime, hash.clone()));
-
-	Ok(hash)
 }
@@ -0,0 +1,212 @@
+use std::{
+	borrow::Cow,
+	collections::BTreeMap,
+	fs::File,
+	io::{self, Write},
+	path::PathBuf,
+	sync::Arc,
+};
+
+use zip::write::SimpleFileOptions;
+
+use crate::{
+	cached_hasher::CachedFileInfo,
+	pack::Pack,
+	responses::ZipResponseWriter,
+	schemas::{
+		CurseforgeManifest, CurseforgeManifestFile, CurseforgeManifestMinecraft, CurseforgeManifestModLoader,
+		CurseforgeManifestType, CurseforgeManifestVersion, DrakermoreModConfig, ModSource, MrpackFile, MrpackFileEnv,
+		MrpackFileHashes, MrpackFormatVersion, MrpackGame, MrpackIndex, PackwizModSide,
+	},
+};
+
+/// A jar in the pack, along with everything the export formats need to know about it
+#[derive(Debug)]
+pub struct ExportedMod {
+	pub name: String,
+	pub jar_file_name: String,
+	/// Where the jar can be downloaded from us
+	pub url: String,
+	pub full_path: PathBuf,
+	pub side: PackwizModSide,
+	pub optional: bool,
+	pub source: Option<ModSource>,
+	pub info: Arc<CachedFileInfo>,
+}
+
+/// A copy dir file, which the export formats bundle into the pack itself
+#[derive(Debug)]
+pub struct ExportedCopyFile {
+	/// Where the file ends up in the instance
+	pub install_path: PathBuf,
+	/// Where the file can be downloaded from us
+	pub url: String,
+	pub full_path: PathBuf,
+	pub side: PackwizModSide,
+	pub info: Arc<CachedFileInfo>,
+}
+
+/// Every file in the pack, taken from the packwiz cache so all the formats agree on what's in the pack
+#[derive(Debug)]
+pub struct ExportedFiles {
+	pub mods: Vec<ExportedMod>,
+	pub copy_files: Vec<ExportedCopyFile>,
+}
+impl ExportedFiles {
+	pub async fn from_pack(pack: &Pack) -> anyhow::Result<Self> {
+		let packwiz = pack.packwiz.read().unwrap().clone();
+		let mut mods = Vec::with_capacity(packwiz.mods.len());
+		for (jar_file_name, cached_mod) in packwiz.mods {
+			let realm = cached_mod.realm;
+			let jar_full_path = pack.download_dir.join(realm.to_string()).join(&jar_file_name);
+			mods.push(ExportedMod {
+				url: format!("{}/jars/{realm}/{jar_file_name}", &pack.url_prefix),
+				info: pack.file_info.get_info_from_file(&jar_full_path).await?,
+				full_path: jar_full_path,
+				name: cached_mod.name,
+				jar_file_name,
+				side: cached_mod.side,
+				optional: cached_mod.optional,
+				source: cached_mod.source,
+			});
+		}
+		let mut copy_files = Vec::with_capacity(packwiz.copy_files.len());
+		for (install_path, cached_file) in packwiz.copy_files {
+			let full_path = pack.copy_dir.join(&cached_file.source);
+			copy_files.push(ExportedCopyFile {
+				install_path,
+				url: format!(
+					"{}/copy_files/{}",
+					&pack.url_prefix,
+					cached_file.source.to_string_lossy()
+				),
+				info: pack.file_info.get_info_from_file(&full_path).await?,
+				full_path,
+				side: cached_file.side,
+			});
+		}
+		Ok(Self { mods, copy_files })
+	}
+
+	/// Adds the copy dir files to the zip, in the folder `override_folder` returns for their side. Files without a
+	/// folder are left out.
+	fn write_copy_files(
+		&self,
+		zip: &mut ZipResponseWriter,
+		override_folder: impl Fn(PackwizModSide) -> Option<&'static str>,
+	) -> anyhow::Result<()> {
+		let zip_options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+		for copy_file in self.copy_files.iter() {
+			let Some(override_folder) = override_folder(copy_file.side) else {
+				continue;
+			};
+			zip.start_file(
+				format!("{override_folder}/{}", copy_file.install_path.to_string_lossy()),
+				zip_options,
+			)?;
+			io::copy(&mut File::open(&copy_file.full_path)?, zip)?;
+		}
+		Ok(())
+	}
+}
+
+/// Writes a Modrinth pack, mods are downloaded from us while copy dir files are bundled as overrides
+pub fn write_mrpack(
+	zip: &mut ZipResponseWriter,
+	modpack: &DrakermoreModConfig,
+	exported_files: &ExportedFiles,
+) -> anyhow::Result<()> {
+	let zip_options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+	zip.start_file("modrinth.index.json", zip_options)?;
+	zip.write_all(&serde_json::to_vec_pretty(&MrpackIndex {
+		format_version: MrpackFormatVersion::V1,
+		game: MrpackGame::Minecraft,
+		version_id: &modpack.pack_version,
+		name: &modpack.name,
+		files: exported_files
+			.mods
+			.iter()
+			.map(|exported_mod| MrpackFile {
+				path: format!("mods/{}", exported_mod.jar_file_name).into(),
+				hashes: MrpackFileHashes {
+					sha1: Cow::Borrowed(&exported_mod.info.sha1),
+					sha512: Cow::Borrowed(&exported_mod.info.sha512),
+				},
+				env: MrpackFileEnv::new(exported_mod.side, exported_mod.optional),
+				downloads: vec![Cow::Borrowed(&exported_mod.url)],
+				file_size: exported_mod.info.size,
+			})
+			.collect(),
+		dependencies: BTreeMap::from([
+			("minecraft", modpack.minecraft_version.as_str()),
+			(modpack.loader.mrpack_dependency(), modpack.loader_version.as_str()),
+		]),
+	})?)?;
+	exported_files.write_copy_files(zip, |side| match side {
+		PackwizModSide::Client => Some("client-overrides"),
+		PackwizModSide::Server => Some("server-overrides"),
+		PackwizModSide::Both => Some("overrides"),
+	})
+}
+
+/// Writes a CurseForge pack. Mods from CurseForge are listed by id, everything else is bundled as overrides. The
+/// CurseForge app only sets up clients, so server-only files are left out.
+pub fn write_curseforge_zip(
+	zip: &mut ZipResponseWriter,
+	modpack: &DrakermoreModConfig,
+	exported_files: &ExportedFiles,
+) -> anyhow::Result<()> {
+	const OVERRIDES_FOLDER: &str = "overrides";
+	let mut files = Vec::new();
+	let mut bundled_mods = Vec::new();
+	for exported_mod in exported_files.mods.iter() {
+		if exported_mod.side == PackwizModSide::Server {
+			continue;
+		}
+		let curseforge_ids = exported_mod
+			.source
+			.as_ref()
+			.map(ModSource::curseforge_ids)
+			.transpose()?
+			.flatten();
+		match curseforge_ids {
+			Some((project_id, file_id)) => files.push(CurseforgeManifestFile {
+				project_id,
+				file_id,
+				required: !exported_mod.optional,
+			}),
+			None => bundled_mods.push(exported_mod),
+		}
+	}
+
+	let zip_options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+	zip.start_file("manifest.json", zip_options)?;
+	zip.write_all(&serde_json::to_vec_pretty(&CurseforgeManifest {
+		minecraft: CurseforgeManifestMinecraft {
+			version: modpack.minecraft_version.clone(),
+			mod_loaders: vec![CurseforgeManifestModLoader {
+				id: format!("{}-{}", modpack.loader, modpack.loader_version),
+				primary: true,
+			}],
+		},
+		manifest_type: CurseforgeManifestType::MinecraftModpack,
+		manifest_version: CurseforgeManifestVersion::V1,
+		name: &modpack.name,
+		version: &modpack.pack_version,
+		author: &modpack.pack_author,
+		files,
+		overrides: OVERRIDES_FOLDER,
+	})?)?;
+	for exported_mod in bundled_mods {
+		zip.start_file(
+			format!("{OVERRIDES_FOLDER}/mods/{}", exported_mod.jar_file_name),
+			// .jar files are already zipped
+			SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+		)?;
+		io::copy(&mut File::open(&exported_mod.full_path)?, zip)?;
+	}
+	exported_files.write_copy_files(zip, |side| match side {
+		PackwizModSide::Client | PackwizModSide::Both => Some(OVERRIDES_FOLDER),
+		PackwizModSide::Server => None,
+	})
+}
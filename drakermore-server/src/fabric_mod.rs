@@ -0,0 +1,158 @@
+use std::{
+	collections::BTreeMap,
+	fmt::Display,
+	io::{Cursor, Read, Seek},
+	path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use zip::{result::ZipError, ZipArchive};
+
+use crate::{fabric_version::FabricVersionRequirement, schemas::PackwizModSide};
+
+/// The subset of `fabric.mod.json` we care about
+/// See https://fabricmc.net/wiki/documentation:fabric_mod_json_spec
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FabricModJson {
+	pub id: String,
+	pub version: String,
+	#[serde(default)]
+	pub name: Option<String>,
+	#[serde(default)]
+	pub environment: FabricModEnvironment,
+	#[serde(default)]
+	pub authors: Vec<FabricModPerson>,
+	/// Links like `homepage`, `sources` and `issues`
+	#[serde(default)]
+	pub contact: BTreeMap<String, String>,
+	/// Mod ids mapped to the versions of them which are needed
+	#[serde(default)]
+	pub depends: BTreeMap<String, FabricVersionRequirement>,
+	/// Mod ids mapped to the versions of them which crash the game when loaded alongside this mod
+	#[serde(default)]
+	pub breaks: BTreeMap<String, FabricVersionRequirement>,
+	/// Like `breaks`, but Fabric only warns about them
+	#[serde(default)]
+	pub conflicts: BTreeMap<String, FabricVersionRequirement>,
+	/// Other mod ids this mod can stand in for
+	#[serde(default)]
+	pub provides: Vec<String>,
+	#[serde(default)]
+	pub jars: Vec<FabricNestedJar>,
+	/// The mods in `jars`, which Fabric loads as if they were separate jars
+	#[serde(skip)]
+	pub nested_mods: Vec<FabricModJson>,
+}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FabricNestedJar {
+	/// Path to the jar inside the outer jar
+	pub file: String,
+}
+impl FabricModJson {
+	/// Reads `fabric.mod.json` from the root of the specified jar file, and from the jars nested in it. Returns `None`
+	/// if the file doesn't exist.
+	pub fn from_jar(jar: impl Read + Seek) -> anyhow::Result<Option<Self>> {
+		let mut archive = ZipArchive::new(jar)?;
+		let Some(mod_json_bytes) = read_zip_entry(&mut archive, "fabric.mod.json")? else {
+			return Ok(None);
+		};
+		let mut fabric_mod: Self = serde_json::from_slice(&mod_json_bytes)?;
+		for nested_jar in fabric_mod.jars.iter() {
+			let Some(nested_jar_bytes) = read_zip_entry(&mut archive, &nested_jar.file)? else {
+				anyhow::bail!("{} lists {} but doesn't contain it", fabric_mod.id, nested_jar.file);
+			};
+			fabric_mod
+				.nested_mods
+				.extend(FabricModJson::from_jar(Cursor::new(nested_jar_bytes))?);
+		}
+		Ok(Some(fabric_mod))
+	}
+	/// Every mod id which this jar satisfies dependencies on
+	pub fn provided_ids(&self) -> Vec<&str> {
+		let mut provided_ids = vec![self.id.as_str()];
+		provided_ids.extend(self.provides.iter().map(String::as_str));
+		for nested_mod in self.nested_mods.iter() {
+			provided_ids.extend(nested_mod.provided_ids());
+		}
+		provided_ids
+	}
+	pub fn display_name(&self) -> &str {
+		self.name.as_deref().unwrap_or(&self.id)
+	}
+}
+impl Display for FabricModJson {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} {}", self.display_name(), self.version)?;
+		for (i, author) in self.authors.iter().enumerate() {
+			f.write_str(if i == 0 { " by " } else { ", " })?;
+			f.write_str(author.name())?;
+		}
+		if let Some(homepage) = self.contact.get("homepage").or_else(|| self.contact.get("sources")) {
+			write!(f, " ({homepage})")?;
+		}
+		Ok(())
+	}
+}
+
+/// A broken fabric.mod.json shouldn't stop a jar from being used, we just won't know anything about the mod in it
+pub fn read_fabric_mod_or_warn(jar_path: &Path, jar_bytes: &[u8]) -> Option<FabricModJson> {
+	FabricModJson::from_jar(Cursor::new(jar_bytes)).unwrap_or_else(|err| {
+		tracing::warn!("Couldn't read fabric.mod.json from {}: {err}", jar_path.display());
+		None
+	})
+}
+
+fn read_zip_entry(archive: &mut ZipArchive<impl Read + Seek>, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+	let mut entry = match archive.by_name(name) {
+		Ok(entry) => entry,
+		Err(ZipError::FileNotFound) => return Ok(None),
+		Err(err) => return Err(err.into()),
+	};
+	let mut entry_bytes = Vec::with_capacity(entry.size() as usize);
+	entry.read_to_end(&mut entry_bytes)?;
+	Ok(Some(entry_bytes))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FabricModEnvironment {
+	#[default]
+	#[serde(rename = "*")]
+	Both,
+	#[serde(rename = "client")]
+	Client,
+	#[serde(rename = "server")]
+	Server,
+}
+impl FabricModEnvironment {
+	/// Narrows down the side a mod was placed in using the environment the mod says it runs in.
+	pub fn narrow_side(self, realm: PackwizModSide) -> PackwizModSide {
+		match (realm, self) {
+			(PackwizModSide::Both, FabricModEnvironment::Client) => PackwizModSide::Client,
+			(PackwizModSide::Both, FabricModEnvironment::Server) => PackwizModSide::Server,
+			_ => realm,
+		}
+	}
+	/// Whether the mod says it only runs on the side the realm doesn't install it on, e.g. a client mod in `server/`
+	pub fn contradicts(self, realm: PackwizModSide) -> bool {
+		matches!(
+			(realm, self),
+			(PackwizModSide::Server, FabricModEnvironment::Client)
+				| (PackwizModSide::Client, FabricModEnvironment::Server)
+		)
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum FabricModPerson {
+	Name(String),
+	Detailed { name: String },
+}
+impl FabricModPerson {
+	pub fn name(&self) -> &str {
+		match self {
+			FabricModPerson::Name(name) => name,
+			FabricModPerson::Detailed { name, .. } => name,
+		}
+	}
+}
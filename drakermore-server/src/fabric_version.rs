@@ -0,0 +1,245 @@
+use std::{cmp::Ordering, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+/// A version as Fabric compares it: semantic versions get compared component by component, anything else can only be
+/// compared for equality.
+/// See https://fabricmc.net/wiki/documentation:fabric_mod_json_spec#versionrange
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FabricVersion {
+	Semantic {
+		components: Vec<u64>,
+		prerelease: Option<String>,
+	},
+	Other(String),
+}
+impl FabricVersion {
+	pub fn parse(version: &str) -> Self {
+		// Build metadata never affects comparisons
+		let without_build = version
+			.split_once('+')
+			.map_or(version, |(without_build, _)| without_build);
+		let (core, prerelease) = match without_build.split_once('-') {
+			Some((core, prerelease)) => (core, Some(prerelease.to_string())),
+			None => (without_build, None),
+		};
+		let components: Result<Vec<u64>, _> = core.split('.').map(str::parse).collect();
+		match components {
+			Ok(components) if !components.is_empty() => FabricVersion::Semantic { components, prerelease },
+			_ => FabricVersion::Other(version.into()),
+		}
+	}
+	/// None unless both versions are semantic
+	fn semantic_cmp(&self, other: &FabricVersion) -> Option<Ordering> {
+		let (
+			FabricVersion::Semantic {
+				components: a_components,
+				prerelease: a_prerelease,
+			},
+			FabricVersion::Semantic {
+				components: b_components,
+				prerelease: b_prerelease,
+			},
+		) = (self, other)
+		else {
+			return None;
+		};
+		for index in 0..a_components.len().max(b_components.len()) {
+			let a = a_components.get(index).copied().unwrap_or(0);
+			let b = b_components.get(index).copied().unwrap_or(0);
+			if a != b {
+				return Some(a.cmp(&b));
+			}
+		}
+		Some(match (a_prerelease, b_prerelease) {
+			(None, None) => Ordering::Equal,
+			(None, Some(_)) => Ordering::Greater,
+			(Some(_), None) => Ordering::Less,
+			(Some(a), Some(b)) => prerelease_cmp(a, b),
+		})
+	}
+	fn component(&self, index: usize) -> Option<u64> {
+		match self {
+			FabricVersion::Semantic { components, .. } => Some(components.get(index).copied().unwrap_or(0)),
+			FabricVersion::Other(_) => None,
+		}
+	}
+	fn matches(&self, operator: FabricVersionOperator, required: &FabricVersion) -> bool {
+		let ordering = self.semantic_cmp(required);
+		match operator {
+			FabricVersionOperator::Equal => match ordering {
+				Some(ordering) => ordering == Ordering::Equal,
+				None => self == required,
+			},
+			FabricVersionOperator::Greater => ordering == Some(Ordering::Greater),
+			FabricVersionOperator::GreaterOrEqual => ordering.is_some_and(Ordering::is_ge),
+			FabricVersionOperator::Less => ordering == Some(Ordering::Less),
+			FabricVersionOperator::LessOrEqual => ordering.is_some_and(Ordering::is_le),
+			FabricVersionOperator::SameMinor => {
+				ordering.is_some_and(Ordering::is_ge)
+					&& self.component(0) == required.component(0)
+					&& self.component(1) == required.component(1)
+			},
+			FabricVersionOperator::SameMajor => {
+				ordering.is_some_and(Ordering::is_ge) && self.component(0) == required.component(0)
+			},
+		}
+	}
+}
+impl Display for FabricVersion {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			FabricVersion::Semantic { components, prerelease } => {
+				for (index, component) in components.iter().enumerate() {
+					if index > 0 {
+						f.write_str(".")?;
+					}
+					write!(f, "{component}")?;
+				}
+				if let Some(prerelease) = prerelease {
+					write!(f, "-{prerelease}")?;
+				}
+				Ok(())
+			},
+			FabricVersion::Other(version) => f.write_str(version),
+		}
+	}
+}
+
+/// Dot-separated identifiers, numeric ones sort before and are compared as numbers
+fn prerelease_cmp(a: &str, b: &str) -> Ordering {
+	let mut a_identifiers = a.split('.');
+	let mut b_identifiers = b.split('.');
+	loop {
+		let ordering = match (a_identifiers.next(), b_identifiers.next()) {
+			(None, None) => return Ordering::Equal,
+			(None, Some(_)) => return Ordering::Less,
+			(Some(_), None) => return Ordering::Greater,
+			(Some(a), Some(b)) => match (a.parse::<u64>(), b.parse::<u64>()) {
+				(Ok(a), Ok(b)) => a.cmp(&b),
+				(Ok(_), Err(_)) => Ordering::Less,
+				(Err(_), Ok(_)) => Ordering::Greater,
+				(Err(_), Err(_)) => a.cmp(b),
+			},
+		};
+		if ordering != Ordering::Equal {
+			return ordering;
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FabricVersionOperator {
+	Equal,
+	Greater,
+	GreaterOrEqual,
+	Less,
+	LessOrEqual,
+	/// `~`
+	SameMinor,
+	/// `^`
+	SameMajor,
+}
+
+/// The version ranges in `depends`, `breaks` etc. Fabric accepts either a single range or a list of them, and a
+/// version matches the list if it matches any of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FabricVersionRequirement {
+	Single(String),
+	AnyOf(Vec<String>),
+}
+impl FabricVersionRequirement {
+	fn ranges(&self) -> &[String] {
+		match self {
+			FabricVersionRequirement::Single(range) => std::slice::from_ref(range),
+			FabricVersionRequirement::AnyOf(ranges) => ranges,
+		}
+	}
+	pub fn matches(&self, version: &FabricVersion) -> bool {
+		self.ranges().iter().any(|range| range_matches(range, version))
+	}
+}
+impl Display for FabricVersionRequirement {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.ranges() {
+			[] => f.write_str("nothing"),
+			ranges => f.write_str(&ranges.join(" || ")),
+		}
+	}
+}
+
+/// A range is made up of space-separated terms, all of which have to match
+fn range_matches(range: &str, version: &FabricVersion) -> bool {
+	range.split_whitespace().all(|term| term_matches(term, version))
+}
+fn term_matches(term: &str, version: &FabricVersion) -> bool {
+	if term == "*" {
+		return true;
+	}
+	let (operator, required) = [
+		(">=", FabricVersionOperator::GreaterOrEqual),
+		("<=", FabricVersionOperator::LessOrEqual),
+		(">", FabricVersionOperator::Greater),
+		("<", FabricVersionOperator::Less),
+		("=", FabricVersionOperator::Equal),
+		("~", FabricVersionOperator::SameMinor),
+		("^", FabricVersionOperator::SameMajor),
+	]
+	.into_iter()
+	.find_map(|(prefix, operator)| term.strip_prefix(prefix).map(|required| (operator, required)))
+	.unwrap_or((FabricVersionOperator::Equal, term));
+	// `1.20.x` matches every version starting with 1.20
+	let components: Vec<&str> = required.split('.').collect();
+	if let Some(wildcard_index) = components
+		.iter()
+		.position(|component| matches!(*component, "x" | "X" | "*"))
+	{
+		return components[..wildcard_index]
+			.iter()
+			.enumerate()
+			.all(|(index, component)| component.parse().ok() == version.component(index));
+	}
+	version.matches(operator, &FabricVersion::parse(required))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matches(range: &str, version: &str) -> bool {
+		FabricVersionRequirement::Single(range.into()).matches(&FabricVersion::parse(version))
+	}
+
+	#[test]
+	fn semantic_ranges() {
+		assert!(matches("*", "whatever"));
+		assert!(matches(">=0.15.0", "0.16.5"));
+		assert!(!matches(">=0.15.0", "0.14.21"));
+		assert!(matches(">=1.20 <1.21", "1.20.1"));
+		assert!(!matches(">=1.20 <1.21", "1.21"));
+		assert!(matches("~1.20.1", "1.20.4"));
+		assert!(!matches("~1.20.1", "1.21.0"));
+		assert!(matches("^0.5.0", "0.9.0"));
+		assert!(!matches("^1.0.0", "2.0.0"));
+		assert!(matches("1.20.x", "1.20.6"));
+		assert!(!matches("1.20.x", "1.19.4"));
+		assert!(matches("1.20", "1.20.0"));
+		assert!(matches("0.5.8", "0.5.8+mc1.20.1"));
+	}
+
+	#[test]
+	fn prereleases_come_before_releases() {
+		assert!(!matches(">=1.0.0", "1.0.0-beta.2"));
+		assert!(matches(">=1.0.0-beta.2", "1.0.0-beta.10"));
+		assert!(matches("<1.0.0-rc", "1.0.0-beta"));
+	}
+
+	#[test]
+	fn other_versions_only_match_exactly() {
+		assert!(matches("mc1.20.1-0.4.10", "mc1.20.1-0.4.10"));
+		assert!(!matches(">=mc1.20.1-0.4.10", "mc1.20.1-0.4.10"));
+		assert!(FabricVersionRequirement::AnyOf(vec!["1.19.2".into(), "1.20.x".into()])
+			.matches(&FabricVersion::parse("1.20.1")));
+	}
+}
@@ -0,0 +1,711 @@
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fmt::Display,
+	path::Path,
+};
+
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{
+	fabric_mod::{read_fabric_mod_or_warn, FabricModJson},
+	fabric_version::{FabricVersion, FabricVersionRequirement},
+	pack::Pack,
+	packwiz_cache::{find_jar_realms, PackwizCache},
+	schemas::{DrakermoreModConfig, ModLoader, PackwizModSide},
+};
+
+/// Dependencies we can't check, so they're assumed to be fine
+const UNCHECKED_MOD_IDS: [&str; 1] = ["java"];
+
+/// A jar as the checker sees it
+#[derive(Debug, Clone)]
+pub struct CheckedJar {
+	pub jar_file_name: String,
+	/// The download dir folder the jar is in
+	pub realm: PackwizModSide,
+	/// The side the jar gets installed on, after narrowing it down with its fabric.mod.json
+	pub side: PackwizModSide,
+	pub fabric_mod: Option<FabricModJson>,
+}
+impl CheckedJar {
+	/// The jar's path relative to the download dir
+	fn path(&self) -> String {
+		format!("{}/{}", self.realm, self.jar_file_name)
+	}
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum JarProblemKind {
+	#[serde(rename = "missing_dependency")]
+	MissingDependency,
+	#[serde(rename = "wrong_dependency_version")]
+	WrongDependencyVersion,
+	#[serde(rename = "breaks")]
+	Breaks,
+	#[serde(rename = "conflicts")]
+	Conflicts,
+	/// More than one jar has the same mod id, or `provides` it
+	#[serde(rename = "duplicate_mod")]
+	DuplicateMod,
+	/// More than one realm has a jar with the same file name, only one of which can be served
+	#[serde(rename = "duplicate_file_name")]
+	DuplicateFileName,
+	/// The jar's fabric.mod.json says it only runs on the side its realm doesn't install it on
+	#[serde(rename = "wrong_realm")]
+	WrongRealm,
+}
+impl JarProblemKind {
+	/// Fabric refuses to launch with anything but conflicts, which it only warns about. Duplicate file names don't
+	/// crash anything, but which copy gets served depends on the realm order. Jars in the wrong realm don't crash
+	/// anything either, but they're missing from the side they're actually for.
+	pub fn is_fatal(self) -> bool {
+		self != JarProblemKind::Conflicts
+	}
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct JarProblem {
+	pub kind: JarProblemKind,
+	/// The side the problem shows up on
+	pub side: PackwizModSide,
+	pub jar_file_name: String,
+	pub mod_id: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub other_mod_id: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub requirement: Option<String>,
+	/// The versions of the other mod which the pack has and where they come from, or the duplicate jars
+	pub found: Vec<String>,
+	pub message: String,
+}
+impl Display for JarProblem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.message)
+	}
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct JarCheckReport {
+	/// False if any problem would stop the game from launching
+	pub ok: bool,
+	pub problems: Vec<JarProblem>,
+}
+
+/// Something which gives a mod id a version
+#[derive(Debug)]
+struct Provider<'a> {
+	version: FabricVersion,
+	source: &'a str,
+}
+
+/// Checks every jar's `depends`, `breaks` and `conflicts` against the other jars installed on the same side, and
+/// looks for duplicate jars
+pub fn check_jars(modpack: &DrakermoreModConfig, jars: &[CheckedJar]) -> JarCheckReport {
+	let mut problems = merge_sides(
+		check_side(modpack, jars, PackwizModSide::Client),
+		check_side(modpack, jars, PackwizModSide::Server),
+	);
+	problems.extend(duplicate_problems(jars));
+	problems.extend(wrong_realm_problems(jars));
+	JarCheckReport {
+		ok: !problems.iter().any(|problem| problem.kind.is_fatal()),
+		problems,
+	}
+}
+
+/// Finds mod ids which more than one jar on the same side has, and file names which more than one realm has
+pub fn duplicate_problems(jars: &[CheckedJar]) -> Vec<JarProblem> {
+	let mut problems = merge_sides(
+		duplicate_mods(jars, PackwizModSide::Client),
+		duplicate_mods(jars, PackwizModSide::Server),
+	);
+	let mut jars_by_file_name: BTreeMap<&str, Vec<&CheckedJar>> = BTreeMap::new();
+	for jar in jars.iter() {
+		jars_by_file_name.entry(&jar.jar_file_name).or_default().push(jar);
+	}
+	for (jar_file_name, same_name_jars) in jars_by_file_name {
+		if same_name_jars.len() < 2 {
+			continue;
+		}
+		let mut problem = JarProblem {
+			kind: JarProblemKind::DuplicateFileName,
+			side: same_name_jars
+				.iter()
+				.map(|jar| jar.realm)
+				.reduce(PackwizModSide::union)
+				.unwrap_or(PackwizModSide::Both),
+			jar_file_name: jar_file_name.into(),
+			mod_id: same_name_jars[0]
+				.fabric_mod
+				.as_ref()
+				.map_or(jar_file_name, |fabric_mod| &fabric_mod.id)
+				.into(),
+			other_mod_id: None,
+			requirement: None,
+			found: same_name_jars.iter().map(|jar| jar.path()).collect(),
+			message: String::new(),
+		};
+		problem.message = problem.describe();
+		problems.push(problem);
+	}
+	problems
+}
+
+/// Finds jars whose fabric.mod.json environment doesn't overlap with the realm they're in
+pub fn wrong_realm_problems(jars: &[CheckedJar]) -> Vec<JarProblem> {
+	let mut problems = Vec::new();
+	for jar in jars.iter() {
+		let Some(fabric_mod) = jar
+			.fabric_mod
+			.as_ref()
+			.filter(|fabric_mod| fabric_mod.environment.contradicts(jar.realm))
+		else {
+			continue;
+		};
+		let mut problem = JarProblem {
+			kind: JarProblemKind::WrongRealm,
+			side: jar.realm,
+			jar_file_name: jar.jar_file_name.clone(),
+			mod_id: fabric_mod.id.clone(),
+			other_mod_id: None,
+			requirement: None,
+			found: vec![jar.path()],
+			message: String::new(),
+		};
+		problem.message = problem.describe();
+		problems.push(problem);
+	}
+	problems
+}
+
+fn duplicate_mods(jars: &[CheckedJar], side: PackwizModSide) -> Vec<JarProblem> {
+	let mut jars_by_mod_id: BTreeMap<&str, Vec<&CheckedJar>> = BTreeMap::new();
+	for jar in jars.iter().filter(|jar| jar.side.intersection(side).is_some()) {
+		let Some(fabric_mod) = &jar.fabric_mod else {
+			continue;
+		};
+		let mod_ids: BTreeSet<&str> = [fabric_mod.id.as_str()]
+			.into_iter()
+			.chain(fabric_mod.provides.iter().map(String::as_str))
+			.collect();
+		for mod_id in mod_ids {
+			jars_by_mod_id.entry(mod_id).or_default().push(jar);
+		}
+	}
+	let mut problems = Vec::new();
+	for (mod_id, same_mod_jars) in jars_by_mod_id {
+		// Copies of the same jar in different realms already get reported as duplicate file names
+		let file_names: BTreeSet<&str> = same_mod_jars.iter().map(|jar| jar.jar_file_name.as_str()).collect();
+		if file_names.len() < 2 {
+			continue;
+		}
+		let mut problem = JarProblem {
+			kind: JarProblemKind::DuplicateMod,
+			side,
+			jar_file_name: same_mod_jars[0].jar_file_name.clone(),
+			mod_id: mod_id.into(),
+			other_mod_id: None,
+			requirement: None,
+			found: same_mod_jars.iter().map(|jar| jar.path()).collect(),
+			message: String::new(),
+		};
+		problem.message = problem.describe();
+		problems.push(problem);
+	}
+	problems
+}
+
+/// Problems which show up the same way on both sides get reported once
+fn merge_sides(client_problems: Vec<JarProblem>, mut server_problems: Vec<JarProblem>) -> Vec<JarProblem> {
+	let mut problems = Vec::with_capacity(client_problems.len() + server_problems.len());
+	for mut problem in client_problems {
+		let server_index = server_problems
+			.iter()
+			.position(|server_problem| server_problem.same_apart_from_side(&problem));
+		if let Some(server_index) = server_index {
+			server_problems.remove(server_index);
+			problem.side = PackwizModSide::Both;
+			problem.message = problem.describe();
+		}
+		problems.push(problem);
+	}
+	problems.extend(server_problems);
+	problems
+}
+
+fn check_side(modpack: &DrakermoreModConfig, jars: &[CheckedJar], side: PackwizModSide) -> Vec<JarProblem> {
+	let loaded_mods: Vec<(&CheckedJar, &FabricModJson)> = jars
+		.iter()
+		.filter(|jar| jar.side.intersection(side).is_some())
+		.filter_map(|jar| Some(jar).zip(jar.fabric_mod.as_ref()))
+		.flat_map(|(jar, fabric_mod)| {
+			all_mods(fabric_mod)
+				.into_iter()
+				.map(move |fabric_mod| (jar, fabric_mod))
+		})
+		.collect();
+
+	let mut providers: HashMap<&str, Vec<Provider>> = HashMap::new();
+	let config_source = "the drakermore config";
+	providers.entry("minecraft").or_default().push(Provider {
+		version: FabricVersion::parse(&modpack.minecraft_version),
+		source: config_source,
+	});
+	if let Some(loader_id) = fabric_loader_mod_id(modpack.loader) {
+		providers.entry(loader_id).or_default().push(Provider {
+			version: FabricVersion::parse(&modpack.loader_version),
+			source: config_source,
+		});
+	}
+	for (jar, fabric_mod) in loaded_mods.iter() {
+		for provided_id in [fabric_mod.id.as_str()]
+			.into_iter()
+			.chain(fabric_mod.provides.iter().map(String::as_str))
+		{
+			providers.entry(provided_id).or_default().push(Provider {
+				version: FabricVersion::parse(&fabric_mod.version),
+				source: &jar.jar_file_name,
+			});
+		}
+	}
+
+	let mut problems = Vec::new();
+	for (jar, fabric_mod) in loaded_mods.iter() {
+		let mut problem = |kind, other_mod_id: &str, requirement: &FabricVersionRequirement, found: &[&Provider]| {
+			let mut problem = JarProblem {
+				kind,
+				side,
+				jar_file_name: jar.jar_file_name.clone(),
+				mod_id: fabric_mod.id.clone(),
+				other_mod_id: Some(other_mod_id.into()),
+				requirement: Some(requirement.to_string()),
+				found: found
+					.iter()
+					.map(|provider| format!("{} ({})", provider.version, provider.source))
+					.collect(),
+				message: String::new(),
+			};
+			problem.message = problem.describe();
+			problems.push(problem);
+		};
+		for (dependency_id, requirement) in fabric_mod.depends.iter() {
+			if UNCHECKED_MOD_IDS.contains(&dependency_id.as_str())
+				|| (dependency_id == "fabricloader" && modpack.loader != ModLoader::Fabric)
+			{
+				continue;
+			}
+			let found: Vec<_> = providers.get(dependency_id.as_str()).into_iter().flatten().collect();
+			if found.is_empty() {
+				problem(JarProblemKind::MissingDependency, dependency_id, requirement, &[]);
+			} else if !found.iter().any(|provider| requirement.matches(&provider.version)) {
+				problem(
+					JarProblemKind::WrongDependencyVersion,
+					dependency_id,
+					requirement,
+					&found,
+				);
+			}
+		}
+		for (kind, entries) in [
+			(JarProblemKind::Breaks, &fabric_mod.breaks),
+			(JarProblemKind::Conflicts, &fabric_mod.conflicts),
+		] {
+			for (other_mod_id, requirement) in entries.iter() {
+				let found: Vec<_> = providers
+					.get(other_mod_id.as_str())
+					.into_iter()
+					.flatten()
+					.filter(|provider| provider.source != jar.jar_file_name && requirement.matches(&provider.version))
+					.collect();
+				if !found.is_empty() {
+					problem(kind, other_mod_id, requirement, &found);
+				}
+			}
+		}
+	}
+	problems
+}
+
+/// Which of the mod's (and its nested mods') Minecraft and loader version ranges the config's versions fall outside of
+pub fn version_incompatibilities(modpack: &DrakermoreModConfig, fabric_mod: &FabricModJson) -> Vec<String> {
+	let loader_version = fabric_loader_mod_id(modpack.loader).map(|loader_id| (loader_id, &modpack.loader_version));
+	let mut incompatibilities = Vec::new();
+	for fabric_mod in all_mods(fabric_mod) {
+		for (dependency_id, configured_version) in [("minecraft", &modpack.minecraft_version)]
+			.into_iter()
+			.chain(loader_version)
+		{
+			let Some(requirement) = fabric_mod.depends.get(dependency_id) else {
+				continue;
+			};
+			if !requirement.matches(&FabricVersion::parse(configured_version)) {
+				incompatibilities.push(format!(
+					"{} needs {dependency_id} {requirement}, but the pack is on {configured_version}",
+					fabric_mod.id
+				));
+			}
+		}
+	}
+	incompatibilities
+}
+
+/// The mod id fabric.mod.json files can depend on the pack's loader by. Quilt stands in for whichever Fabric Loader
+/// version mods want, and Forge and NeoForge versions have nothing to do with Fabric Loader versions.
+fn fabric_loader_mod_id(loader: ModLoader) -> Option<&'static str> {
+	match loader {
+		ModLoader::Fabric => Some("fabricloader"),
+		ModLoader::Quilt => Some("quilt_loader"),
+		ModLoader::Forge | ModLoader::NeoForge => None,
+	}
+}
+
+/// The mod itself and every mod nested inside it, since Fabric loads those too
+fn all_mods(fabric_mod: &FabricModJson) -> Vec<&FabricModJson> {
+	let mut mods = vec![fabric_mod];
+	for nested_mod in fabric_mod.nested_mods.iter() {
+		mods.extend(all_mods(nested_mod));
+	}
+	mods
+}
+
+impl JarProblem {
+	fn same_apart_from_side(&self, other: &JarProblem) -> bool {
+		self.kind == other.kind
+			&& self.jar_file_name == other.jar_file_name
+			&& self.mod_id == other.mod_id
+			&& self.other_mod_id == other.other_mod_id
+			&& self.requirement == other.requirement
+			&& self.found == other.found
+	}
+	fn describe(&self) -> String {
+		let subject = format!("[{}] {} ({})", self.side, self.mod_id, self.jar_file_name);
+		let other_mod = format!(
+			"{} {}",
+			self.other_mod_id.as_deref().unwrap_or_default(),
+			self.requirement.as_deref().unwrap_or_default()
+		);
+		let found = self.found.join(", ");
+		match self.kind {
+			JarProblemKind::MissingDependency => format!("{subject} depends on {other_mod}, which isn't in the pack"),
+			JarProblemKind::WrongDependencyVersion => {
+				format!("{subject} depends on {other_mod}, but the pack has {found}")
+			},
+			JarProblemKind::Breaks => format!("{subject} breaks with {other_mod}, which the pack has: {found}"),
+			JarProblemKind::Conflicts => format!("{subject} conflicts with {other_mod}, which the pack has: {found}"),
+			JarProblemKind::DuplicateMod => format!("[{}] {} is in more than one jar: {found}", self.side, self.mod_id),
+			JarProblemKind::DuplicateFileName => format!(
+				"[{}] {} is in more than one realm: {found}, only {} is served",
+				self.side,
+				self.jar_file_name,
+				self.found.first().map(String::as_str).unwrap_or_default()
+			),
+			JarProblemKind::WrongRealm => {
+				format!("{subject} doesn't run on the {} side, but it's in {found}", self.side)
+			},
+		}
+	}
+}
+
+/// Reads every jar straight from the download dir's realm folders
+pub async fn download_dir_jars(download_dir: &Path) -> anyhow::Result<Vec<CheckedJar>> {
+	let mut jars = Vec::new();
+	for realm in PackwizModSide::all() {
+		let mut dir_reader = fs::read_dir(download_dir.join(realm.to_string())).await?;
+		while let Some(dir_entry) = dir_reader.next_entry().await? {
+			let path = dir_entry.path();
+			if path.extension().is_none_or(|ext| ext != "jar") {
+				continue;
+			}
+			let fabric_mod = read_fabric_mod_or_warn(&path, &fs::read(&path).await?);
+			jars.push(CheckedJar {
+				jar_file_name: dir_entry.file_name().to_string_lossy().into(),
+				realm,
+				side: fabric_mod
+					.as_ref()
+					.map_or(realm, |fabric_mod| fabric_mod.environment.narrow_side(realm)),
+				fabric_mod,
+			});
+		}
+	}
+	Ok(jars)
+}
+
+/// The jars the pack is serving right now, along with the copies they shadow
+pub async fn pack_jars(pack: &Pack) -> anyhow::Result<Vec<CheckedJar>> {
+	let cache = pack.packwiz.read().unwrap().clone();
+	cached_jars(pack, &cache).await
+}
+
+/// The jars in a packwiz cache, along with the copies they shadow
+pub async fn cached_jars(pack: &Pack, cache: &PackwizCache) -> anyhow::Result<Vec<CheckedJar>> {
+	let mut jars = Vec::with_capacity(cache.mods.len());
+	for (jar_file_name, cached_mod) in cache.mods.iter() {
+		let shadowed_realms = cache.shadowed_jars.get(jar_file_name).into_iter().flatten();
+		for (index, realm) in [cached_mod.realm].iter().chain(shadowed_realms).enumerate() {
+			let jar_path = pack.download_dir.join(realm.to_string()).join(jar_file_name);
+			let fabric_mod = pack.file_info.get_info_from_file(&jar_path).await?.fabric_mod.clone();
+			jars.push(CheckedJar {
+				jar_file_name: jar_file_name.clone(),
+				realm: *realm,
+				side: match (index, &fabric_mod) {
+					(0, _) => cached_mod.side,
+					(_, Some(fabric_mod)) => fabric_mod.environment.narrow_side(*realm),
+					(_, None) => *realm,
+				},
+				fabric_mod,
+			});
+		}
+	}
+	// A jar whose first copy was left out of the pack still shadows the copies in the other realms
+	for jar_file_name in cache
+		.shadowed_jars
+		.keys()
+		.filter(|jar_file_name| !cache.mods.contains_key(*jar_file_name))
+	{
+		for realm in find_jar_realms(pack, Path::new(jar_file_name)).await? {
+			let jar_path = pack.download_dir.join(realm.to_string()).join(jar_file_name);
+			let fabric_mod = pack.file_info.get_info_from_file(&jar_path).await?.fabric_mod.clone();
+			jars.push(CheckedJar {
+				jar_file_name: jar_file_name.clone(),
+				realm,
+				side: fabric_mod
+					.as_ref()
+					.map_or(realm, |fabric_mod| fabric_mod.environment.narrow_side(realm)),
+				fabric_mod,
+			});
+		}
+	}
+	Ok(jars)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::{
+		packwiz_cache::rebuild_packwiz_cache,
+		test_utils::{test_config, test_pack, write_jar},
+	};
+
+	fn jar(jar_file_name: &str, side: PackwizModSide, fabric_mod: serde_json::Value) -> CheckedJar {
+		CheckedJar {
+			jar_file_name: jar_file_name.into(),
+			realm: side,
+			side,
+			fabric_mod: Some(serde_json::from_value(fabric_mod).unwrap()),
+		}
+	}
+
+	#[test]
+	fn problems_are_checked_per_side() {
+		let modpack = test_config("");
+		let jars = [
+			jar(
+				"iris.jar",
+				PackwizModSide::Client,
+				json!({ "id": "iris", "version": "1.7.0", "depends": { "sodium": "0.5.x", "minecraft": "1.20.x" } }),
+			),
+			jar(
+				"sodium.jar",
+				PackwizModSide::Client,
+				json!({ "id": "sodium", "version": "0.6.0" }),
+			),
+			jar(
+				"spell-power.jar",
+				PackwizModSide::Both,
+				json!({ "id": "spell_power", "version": "0.10.0", "depends": { "fabricloader": ">=0.15", "cloth-config": "*" } }),
+			),
+			jar(
+				"lithium.jar",
+				PackwizModSide::Both,
+				json!({ "id": "lithium", "version": "0.11.2", "provides": ["lithium-api"] }),
+			),
+			jar(
+				"old-optimizer.jar",
+				PackwizModSide::Server,
+				json!({ "id": "old_optimizer", "version": "1.0", "breaks": { "lithium-api": "*" }, "conflicts": { "sodium": "*" } }),
+			),
+		];
+
+		let report = check_jars(&modpack, &jars);
+		let problems: Vec<_> = report
+			.problems
+			.iter()
+			.map(|problem| {
+				(
+					problem.kind,
+					problem.side,
+					problem.mod_id.as_str(),
+					problem.other_mod_id.as_deref().unwrap_or_default(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			problems,
+			[
+				(
+					JarProblemKind::WrongDependencyVersion,
+					PackwizModSide::Client,
+					"iris",
+					"sodium"
+				),
+				(
+					JarProblemKind::MissingDependency,
+					PackwizModSide::Both,
+					"spell_power",
+					"cloth-config"
+				),
+				(
+					JarProblemKind::Breaks,
+					PackwizModSide::Server,
+					"old_optimizer",
+					"lithium-api"
+				),
+			]
+		);
+		assert!(!report.ok);
+		assert_eq!(
+			report.problems[0].message,
+			"[client] iris (iris.jar) depends on sodium 0.5.x, but the pack has 0.6.0 (sodium.jar)"
+		);
+	}
+
+	#[test]
+	fn jars_for_other_minecraft_versions_are_incompatible() {
+		let mut modpack = test_config("");
+		let fabric_mod: FabricModJson = serde_json::from_value(json!({
+			"id": "sodium",
+			"version": "0.6.0",
+			"depends": { "minecraft": "~1.21", "fabricloader": ">=0.16" },
+		}))
+		.unwrap();
+		assert_eq!(
+			version_incompatibilities(&modpack, &fabric_mod),
+			["sodium needs minecraft ~1.21, but the pack is on 1.20.1"]
+		);
+		modpack.minecraft_version = "1.21.1".into();
+		assert!(version_incompatibilities(&modpack, &fabric_mod).is_empty());
+
+		// NeoForge's version numbers aren't Fabric Loader versions, so they can't be checked against each other
+		modpack.loader = ModLoader::NeoForge;
+		modpack.loader_version = "21.1.65".into();
+		assert!(version_incompatibilities(&modpack, &fabric_mod).is_empty());
+		let jars = [CheckedJar {
+			jar_file_name: "sodium.jar".into(),
+			realm: PackwizModSide::Client,
+			side: PackwizModSide::Client,
+			fabric_mod: Some(fabric_mod),
+		}];
+		assert!(check_jars(&modpack, &jars).problems.is_empty());
+	}
+
+	#[test]
+	fn duplicate_mods_and_file_names_are_found() {
+		let mut shadowed_copy = jar(
+			"sodium-0.5.8.jar",
+			PackwizModSide::Client,
+			json!({ "id": "sodium", "version": "0.5.8" }),
+		);
+		shadowed_copy.realm = PackwizModSide::Both;
+		shadowed_copy.side = PackwizModSide::Both;
+		let jars = [
+			jar(
+				"sodium-0.5.8.jar",
+				PackwizModSide::Client,
+				json!({ "id": "sodium", "version": "0.5.8" }),
+			),
+			shadowed_copy,
+			jar(
+				"sodium-0.5.3.jar",
+				PackwizModSide::Both,
+				json!({ "id": "sodium", "version": "0.5.3" }),
+			),
+			jar(
+				"lithium.jar",
+				PackwizModSide::Both,
+				json!({ "id": "lithium", "version": "0.11.2" }),
+			),
+			jar(
+				"lithium-fork.jar",
+				PackwizModSide::Server,
+				json!({ "id": "lithium_fork", "version": "0.11.2", "provides": ["lithium"] }),
+			),
+		];
+
+		let messages: Vec<_> = duplicate_problems(&jars)
+			.into_iter()
+			.map(|problem| problem.message)
+			.collect();
+		assert_eq!(
+			messages,
+			[
+				"[client] sodium is in more than one jar: client/sodium-0.5.8.jar, both/sodium-0.5.8.jar, both/sodium-0.5.3.jar",
+				"[server] lithium is in more than one jar: both/lithium.jar, server/lithium-fork.jar",
+				"[server] sodium is in more than one jar: both/sodium-0.5.8.jar, both/sodium-0.5.3.jar",
+				"[both] sodium-0.5.8.jar is in more than one realm: client/sodium-0.5.8.jar, both/sodium-0.5.8.jar, only client/sodium-0.5.8.jar is served",
+			]
+		);
+	}
+
+	#[test]
+	fn jars_in_the_wrong_realm_are_problems() {
+		let jars = [
+			jar(
+				"sodium.jar",
+				PackwizModSide::Server,
+				json!({ "id": "sodium", "version": "0.6.0", "environment": "client" }),
+			),
+			jar(
+				"ledger.jar",
+				PackwizModSide::Client,
+				json!({ "id": "ledger", "version": "1.3.0", "environment": "server" }),
+			),
+			jar(
+				"iris.jar",
+				PackwizModSide::Both,
+				json!({ "id": "iris", "version": "1.7.0", "environment": "client" }),
+			),
+			jar(
+				"lithium.jar",
+				PackwizModSide::Server,
+				json!({ "id": "lithium", "version": "0.11.2", "environment": "*" }),
+			),
+		];
+
+		let report = check_jars(&test_config(""), &jars);
+		let messages: Vec<_> = report.problems.iter().map(|problem| problem.message.as_str()).collect();
+		assert_eq!(
+			messages,
+			[
+				"[server] sodium (sodium.jar) doesn't run on the server side, but it's in server/sodium.jar",
+				"[client] ledger (ledger.jar) doesn't run on the client side, but it's in client/ledger.jar",
+			]
+		);
+		assert!(!report.ok);
+	}
+
+	#[tokio::test]
+	async fn shadowed_copies_of_excluded_jars_are_duplicates() {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let pack = test_pack(temp_dir.path(), "exclude_incompatible_jars = true");
+		for realm in ["client", "both"] {
+			write_jar(
+				&pack.download_dir.join(realm).join("sodium.jar"),
+				r#"{ "schemaVersion": 1, "id": "sodium", "version": "0.6.0", "depends": { "minecraft": "~1.21" } }"#,
+			);
+		}
+		rebuild_packwiz_cache(&pack).await.unwrap();
+		assert!(pack.packwiz.read().unwrap().mods.is_empty());
+
+		let messages: Vec<_> = duplicate_problems(&pack_jars(&pack).await.unwrap())
+			.into_iter()
+			.map(|problem| problem.message)
+			.collect();
+		assert_eq!(
+			messages,
+			["[both] sodium.jar is in more than one realm: client/sodium.jar, both/sodium.jar, only client/sodium.jar is served"]
+		);
+	}
+}
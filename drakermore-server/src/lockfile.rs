@@ -0,0 +1,206 @@
+use std::{
+	collections::HashSet,
+	io::ErrorKind as IoErrorKind,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use tokio::fs;
+
+use crate::{
+	schemas::{DrakermoreModConfig, ModListItem, ModSource, PackwizModSide},
+	sync::{find_managed_jars, sidecar_path},
+};
+
+/// Lives next to the drakermore config file
+pub const LOCKFILE_NAME: &str = "drakermore.lock";
+
+/// Records exactly which file each mod list item resolved to when the mods were last synced
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+	#[serde(default)]
+	pub mods: Vec<LockedMod>,
+}
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LockedMod {
+	/// The mod list item's id, which may be a slug rather than the project id
+	pub id: String,
+	pub source: ModSource,
+	pub file_name: String,
+	pub side: PackwizModSide,
+	pub size: u64,
+	/// hex-encoded sha512 hash of the file
+	pub sha512: String,
+	/// Ids of the mods which needed this one, empty if the mod is in the mod list itself
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub required_by: Vec<String>,
+}
+impl LockedMod {
+	pub fn locks(&self, mod_list_item: &ModListItem) -> bool {
+		self.required_by.is_empty() && self.id == mod_list_item.id && self.source.repo == mod_list_item.repo
+	}
+	pub fn jar_path(&self, download_dir: &Path) -> PathBuf {
+		download_dir.join(self.side.to_string()).join(&self.file_name)
+	}
+}
+
+/// A way in which the mod list or the download dir differs from the lockfile
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum LockfileMismatch {
+	#[error("{0} is in the mod list but not in the lockfile")]
+	Unlocked(String),
+	#[error("{0} is in the lockfile but not in the mod list")]
+	NotInModList(String),
+	#[error("{0} was only needed by mods which aren't in the lockfile")]
+	Orphaned(String),
+	#[error("{0} is missing")]
+	MissingJar(PathBuf),
+	#[error("{0} isn't the file which was locked")]
+	ChangedJar(PathBuf),
+	#[error("{0} came from somewhere other than where the lockfile says")]
+	ChangedSource(PathBuf),
+	#[error("{0} was downloaded from a mod repo but isn't in the lockfile")]
+	UnlockedJar(PathBuf),
+}
+
+/// What the server does with a pack whose download dir doesn't match its lockfile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockfileCheck {
+	Ignore,
+	Warn,
+	Refuse,
+}
+impl FromStr for LockfileCheck {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"ignore" => Ok(LockfileCheck::Ignore),
+			"warn" => Ok(LockfileCheck::Warn),
+			"refuse" => Ok(LockfileCheck::Refuse),
+			_ => Err(format!("\"{s}\" should be \"ignore\", \"warn\" or \"refuse\"")),
+		}
+	}
+}
+pub fn lockfile_path(config_path: &Path) -> PathBuf {
+	config_path.parent().unwrap_or(Path::new(".")).join(LOCKFILE_NAME)
+}
+
+impl Lockfile {
+	/// Returns None if the pack hasn't been locked yet
+	pub async fn read_from_file(file_path: &Path) -> anyhow::Result<Option<Self>> {
+		match fs::read_to_string(file_path).await {
+			Ok(lockfile) => Ok(Some(toml::from_str(&lockfile)?)),
+			Err(err) if err.kind() == IoErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err.into()),
+		}
+	}
+	pub async fn write_to_file(&self, file_path: &Path) -> anyhow::Result<()> {
+		let mut lockfile = String::from("# Written by `drakermore-server sync`, don't edit this by hand\n\n");
+		lockfile.push_str(&toml::to_string_pretty(self)?);
+		// Written next to the lockfile then renamed, so a crash can't leave half a lockfile behind
+		let temp_path = sidecar_path(file_path, ".tmp");
+		fs::write(&temp_path, lockfile).await?;
+		fs::rename(&temp_path, file_path).await?;
+		Ok(())
+	}
+
+	/// Compares the lockfile against the mod list and every jar which was downloaded from a mod repo. Jars which
+	/// were put in the download dir by hand aren't checked.
+	pub async fn verify(
+		&self,
+		modpack: &DrakermoreModConfig,
+		download_dir: &Path,
+	) -> anyhow::Result<Vec<LockfileMismatch>> {
+		let mut mismatches = Vec::new();
+		for mod_list_item in modpack.mod_list.iter() {
+			if !self.mods.iter().any(|locked_mod| locked_mod.locks(mod_list_item)) {
+				mismatches.push(LockfileMismatch::Unlocked(mod_list_item.id.clone()));
+			}
+		}
+		let mut locked_paths = HashSet::new();
+		for locked_mod in self.mods.iter() {
+			if locked_mod.required_by.is_empty() {
+				if !modpack
+					.mod_list
+					.iter()
+					.any(|mod_list_item| locked_mod.locks(mod_list_item))
+				{
+					mismatches.push(LockfileMismatch::NotInModList(locked_mod.id.clone()));
+				}
+			} else if !locked_mod
+				.required_by
+				.iter()
+				.any(|required_by| self.mods.iter().any(|other_mod| &other_mod.id == required_by))
+			{
+				mismatches.push(LockfileMismatch::Orphaned(locked_mod.id.clone()));
+			}
+			let jar_path = locked_mod.jar_path(download_dir);
+			locked_paths.insert(jar_path.clone());
+			let jar_bytes = match fs::read(&jar_path).await {
+				Ok(jar_bytes) => jar_bytes,
+				Err(err) if err.kind() == IoErrorKind::NotFound => {
+					mismatches.push(LockfileMismatch::MissingJar(jar_path));
+					continue;
+				},
+				Err(err) => return Err(err.into()),
+			};
+			if jar_bytes.len() as u64 != locked_mod.size
+				|| hex::encode(Sha512::digest(&jar_bytes)) != locked_mod.sha512.to_lowercase()
+			{
+				mismatches.push(LockfileMismatch::ChangedJar(jar_path.clone()));
+			}
+			let source = match fs::read_to_string(sidecar_path(&jar_path, ".source.toml")).await {
+				Ok(source_toml) => Some(toml::from_str::<ModSource>(&source_toml)?),
+				Err(err) if err.kind() == IoErrorKind::NotFound => None,
+				Err(err) => return Err(err.into()),
+			};
+			if source.as_ref() != Some(&locked_mod.source) {
+				mismatches.push(LockfileMismatch::ChangedSource(jar_path));
+			}
+		}
+		for managed_jar in find_managed_jars(download_dir).await? {
+			if !locked_paths.contains(&managed_jar.path) {
+				mismatches.push(LockfileMismatch::UnlockedJar(managed_jar.path));
+			}
+		}
+		Ok(mismatches)
+	}
+}
+
+/// Verifies the download dir against the lockfile next to the config file, if there is one
+pub async fn check_lockfile(
+	config_path: &Path,
+	modpack: &DrakermoreModConfig,
+	download_dir: &Path,
+	lockfile_check: LockfileCheck,
+) -> anyhow::Result<()> {
+	if lockfile_check == LockfileCheck::Ignore {
+		return Ok(());
+	}
+	let lockfile_path = lockfile_path(config_path);
+	let Some(lockfile) = Lockfile::read_from_file(&lockfile_path).await? else {
+		tracing::info!(
+			"{} doesn't exist, not verifying the downloaded mods",
+			lockfile_path.display()
+		);
+		return Ok(());
+	};
+	let mismatches = lockfile.verify(modpack, download_dir).await?;
+	if mismatches.is_empty() {
+		return Ok(());
+	}
+	for mismatch in mismatches.iter() {
+		tracing::warn!("{mismatch}");
+	}
+	if lockfile_check == LockfileCheck::Refuse {
+		anyhow::bail!(
+			"{} doesn't match {} in {} ways, run sync to fix it",
+			download_dir.display(),
+			lockfile_path.display(),
+			mismatches.len()
+		);
+	}
+	Ok(())
+}
@@ -1,95 +1,414 @@
 use std::{
-	borrow::Cow,
-	io::{Error as IoError, ErrorKind as IoErrorKind, Write},
+	io::Write,
 	path::{Path, PathBuf},
-	sync::LazyLock,
+	sync::{Arc, LazyLock},
 };
 
+use admin::{check_admin_token, delete_jar, move_jar, upload_jar};
+use api::{api_mods, api_pack, ApiModFilter};
 use axum::{
-	extract::Path as AxumPath,
-	http::{header, HeaderValue},
-	response::Response,
-	routing::get,
-	Router,
+	body::Body,
+	extract::{Path as AxumPath, Query, Request, State},
+	http::{HeaderMap, StatusCode},
+	middleware::{self, Next},
+	response::{IntoResponse, Response},
+	routing::{get, post, put},
+	Json, Router,
 };
 use bpaf::Bpaf;
-use bytes::Bytes;
-use cached_hasher::get_hash_from_file;
 use crab_nbt::{Nbt, NbtCompound, NbtTag};
-use futures::StreamExt;
-use nested_dirs::subfiles_in_folder;
-use responses::{download_file_name_header, ok_or_anyhow_response, ZipResponse};
-use schemas::{
-	DrakermoreModConfig, MmcPack, PackwizFormatVersion, PackwizHashFormat, PackwizIndex, PackwizIndexFile,
-	PackwizMetadata, PackwizMetadataIndex, PackwizMetadataVersions, PackwizMod, PackwizModDownload, PackwizModSide,
+use exports::{write_curseforge_zip, write_mrpack, ExportedFiles};
+use jar_check::{check_jars, download_dir_jars, pack_jars};
+use lockfile::{check_lockfile, lockfile_path, LockfileCheck};
+use pack::{DrakermoreServerConfig, Pack};
+use packwiz_cache::{rebuild_packwiz_cache, watch_pack, CachedPackwizFile};
+use resolvers::{
+	curseforge::{CurseforgeResolver, CURSEFORGE_API_URL},
+	modrinth::{ModrinthResolver, MODRINTH_API_URL},
+	ModResolver, ResolvedMod, ResolvedModList,
 };
-use sha2::{Digest, Sha512};
-use tokio::fs;
-use tokio_stream::wrappers::ReadDirStream;
-use tower_http::services::ServeDir;
+use responses::{conditional_response, download_file_name_header, ok_or_anyhow_response, ZipResponse};
+use safe_path::{resolve_safe_path, safe_relative_path, SafePathError};
+use schemas::{DrakermoreModConfig, MmcPack, PackwizModSide};
+use server_mods::{link_server_mods, server_mods_drift, ServerModsLink};
+use sync::sync_mods;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
 use zip::write::SimpleFileOptions;
 
-const PACKWIZ_INSTALLER_BOOTSTRAP_JAR: &'static [u8] =
+const PACKWIZ_INSTALLER_BOOTSTRAP_JAR: &[u8] =
 	include_bytes!("../baked_in_files/packwiz-installer-bootstrap.jar");
 
+static JAVA_ARCHIVE_MIME: LazyLock<mime::Mime> =
+	LazyLock::new(|| "application/java-archive".parse().expect("mime type should be valid"));
+
+mod admin;
+mod api;
 mod cached_hasher;
+mod exports;
+mod fabric_mod;
+mod fabric_version;
+mod jar_check;
+mod lockfile;
+mod pack;
+mod packwiz_cache;
+mod resolvers;
 mod responses;
+mod safe_path;
 mod schemas;
+mod server_mods;
+mod sync;
+#[cfg(test)]
+mod test_utils;
 mod nested_dirs;
 
 #[derive(Debug, Clone, Bpaf)]
 #[bpaf(options)]
-pub struct CliOptions {
-	#[bpaf(short, long)]
-	/// Path to drakermore config file
-	pub config: PathBuf,
-	#[bpaf(short('D'), long)]
-	/// The folders in the specified folder will be copied to the user's .minecraft folder verbatim
-	pub copy_dir: PathBuf,
-	#[bpaf(short, long)]
-	/// Path to where the mods where downloaded by the scraper
-	pub download_dir: PathBuf,
+pub enum CliOptions {
+	/// Shows which file each mod in the mod list resolves to, without downloading anything
+	#[bpaf(command)]
+	Resolve {
+		#[bpaf(short, long)]
+		/// Path to drakermore config file
+		config: PathBuf,
+		#[bpaf(external)]
+		repo_options: RepoOptions,
+	},
+	/// Downloads the mod list's mods into the download dir, only touching the mods which changed
+	#[bpaf(command)]
+	Sync {
+		#[bpaf(short, long)]
+		/// Path to drakermore config file
+		config: PathBuf,
+		#[bpaf(short, long)]
+		/// Path to download the mods to
+		download_dir: PathBuf,
+		#[bpaf(external)]
+		repo_options: RepoOptions,
+	},
+	/// Checks that the downloaded mods' dependencies are there and that none of them break each other
+	#[bpaf(command)]
+	Check {
+		#[bpaf(short, long)]
+		/// Path to drakermore config file
+		config: PathBuf,
+		#[bpaf(short, long)]
+		/// Path to where the mods where downloaded
+		download_dir: PathBuf,
+	},
+	/// Replaces a Minecraft server's mods folder with links to the downloaded server and both mods
+	#[bpaf(command)]
+	LinkServerMods {
+		#[bpaf(short, long)]
+		/// Path to where the mods where downloaded
+		download_dir: PathBuf,
+		#[bpaf(short, long)]
+		/// The Minecraft server's mods folder
+		mods_dir: PathBuf,
+		#[bpaf(long, fallback(ServerModsLink::Symlink))]
+		/// Whether to "symlink" or "hardlink" the jars, defaults to "symlink"
+		link: ServerModsLink,
+		#[bpaf(long)]
+		/// Only report how the mods folder differs from the downloaded mods
+		dry_run: bool,
+	},
+	Serve {
+		#[bpaf(external)]
+		serve_options: ServeOptions,
+	},
+}
+
+#[derive(Debug, Clone, Bpaf)]
+pub struct ServeOptions {
+	#[bpaf(external)]
+	pub pack_options: PackOptions,
 	#[bpaf(short, long, fallback("0.0.0.0:3000".to_string()))]
 	/// Address and port to bind to, defaults to "0.0.0.0:3000"
 	pub bind: String,
 	#[bpaf(short('p'), long)]
 	/// The prefix to use for URLs
 	pub url_prefix: String, // Note: https://stackoverflow.com/questions/33218367/
+	#[bpaf(long, fallback(LockfileCheck::Warn))]
+	/// What to do when the downloaded mods don't match drakermore.lock: "ignore", "warn" or "refuse" to serve the
+	/// pack, defaults to "warn"
+	pub lockfile_check: LockfileCheck,
+	#[bpaf(long, fallback(ServerModsLink::Symlink))]
+	/// Whether to "symlink" or "hardlink" jars into the server mods folder, defaults to "symlink"
+	pub server_mods_link: ServerModsLink,
+	#[bpaf(long)]
+	/// File containing the token the admin endpoints need, the DRAKERMORE_ADMIN_TOKEN environment variable is used
+	/// otherwise. The admin endpoints are disabled without one.
+	pub admin_token_file: Option<PathBuf>,
+	#[bpaf(long)]
+	/// File containing a secret every pack URL has to start with, e.g. "{url_prefix}/{secret}/mmc_pack.zip", the
+	/// DRAKERMORE_ACCESS_SECRET environment variable is used otherwise. Packs are public without one.
+	pub access_secret_file: Option<PathBuf>,
 }
 
-// CLI options as a LazyLock so it's accessible globally
-static CLI_OPTIONS: LazyLock<CliOptions> = LazyLock::new(|| {
-	let mut options = cli_options().run();
-	while options.url_prefix.ends_with('/') {
-		options.url_prefix.pop();
+#[derive(Debug, Clone, Bpaf)]
+pub enum PackOptions {
+	/// Serve a single pack at the root
+	Single {
+		#[bpaf(short, long)]
+		/// Path to drakermore config file
+		config: PathBuf,
+		#[bpaf(short('D'), long)]
+		/// The folders in the specified folder will be copied to the user's .minecraft folder verbatim
+		copy_dir: PathBuf,
+		#[bpaf(short, long)]
+		/// Path to where the mods where downloaded by the scraper
+		download_dir: PathBuf,
+		#[bpaf(long)]
+		/// A Minecraft server's mods folder to rebuild from the server and both mods before serving the pack
+		server_mods_dir: Option<PathBuf>,
+	},
+	/// Serve multiple packs under /packs/{name}/
+	Multi {
+		#[bpaf(short('s'), long)]
+		/// Path to a server config listing the packs to serve
+		server_config: PathBuf,
+	},
+}
+
+#[derive(Debug, Clone, Bpaf)]
+pub struct RepoOptions {
+	#[bpaf(long, fallback(MODRINTH_API_URL.to_string()))]
+	/// Base URL of the Modrinth API, defaults to "https://api.modrinth.com/v2"
+	pub modrinth_api_url: String,
+	#[bpaf(long, fallback(CURSEFORGE_API_URL.to_string()))]
+	/// Base URL of the CurseForge API, defaults to "https://api.curseforge.com"
+	pub curseforge_api_url: String,
+	#[bpaf(long)]
+	/// File containing the CurseForge API key, the CURSEFORGE_API_KEY environment variable is used otherwise
+	pub curseforge_api_key_file: Option<PathBuf>,
+}
+impl RepoOptions {
+	async fn mod_resolver(&self) -> anyhow::Result<ModResolver> {
+		let curseforge_api_key = match &self.curseforge_api_key_file {
+			Some(key_file) => Some(tokio::fs::read_to_string(key_file).await?.trim().to_string()),
+			None => std::env::var("CURSEFORGE_API_KEY").ok(),
+		};
+		Ok(ModResolver {
+			modrinth: ModrinthResolver::new(&self.modrinth_api_url)?,
+			curseforge: curseforge_api_key
+				.map(|api_key| CurseforgeResolver::new(&self.curseforge_api_url, api_key))
+				.transpose()?,
+		})
 	}
-	options
-});
+}
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> anyhow::Result<()> {
 	tracing_subscriber::fmt().with_max_level(tracing::Level::DEBUG).init();
-	println!("Pre-hashing .jar files...");
-	pw_index_string().await?;
+	match cli_options().run() {
+		CliOptions::Resolve { config, repo_options } => resolve(&config, repo_options).await,
+		CliOptions::Sync {
+			config,
+			download_dir,
+			repo_options,
+		} => {
+			let modpack = DrakermoreModConfig::read_from_file(&config).await?;
+			let summary = sync_mods(&modpack, &download_dir, &repo_options.mod_resolver().await?).await?;
+			summary.lockfile.write_to_file(&lockfile_path(&config)).await?;
+			println!(
+				"Sync done! {} downloaded, {} moved, {} unchanged, {} removed",
+				summary.downloaded, summary.moved, summary.unchanged, summary.removed
+			);
+			Ok(())
+		},
+		CliOptions::Check { config, download_dir } => {
+			let modpack = DrakermoreModConfig::read_from_file(&config).await?;
+			let report = check_jars(&modpack, &download_dir_jars(&download_dir).await?);
+			for problem in report.problems.iter() {
+				println!("{problem}");
+			}
+			if !report.ok {
+				anyhow::bail!("some mods won't load");
+			}
+			println!("Check done! {} warnings", report.problems.len());
+			Ok(())
+		},
+		CliOptions::LinkServerMods {
+			download_dir,
+			mods_dir,
+			link,
+			dry_run,
+		} => {
+			let download_dir = download_dir.canonicalize()?;
+			if dry_run {
+				for drift in server_mods_drift(&download_dir, &mods_dir).await? {
+					println!("{drift}");
+				}
+				return Ok(());
+			}
+			let summary = link_server_mods(&download_dir, &mods_dir, link).await?;
+			for drift in summary.drift.iter() {
+				println!("{drift}");
+			}
+			if let Some(replaced_dir) = summary.replaced_dir {
+				println!("The previous mods folder was moved to {}", replaced_dir.display());
+			}
+			println!("Linking done! {} mods linked", summary.linked);
+			Ok(())
+		},
+		CliOptions::Serve { serve_options } => serve(serve_options).await,
+	}
+}
+
+async fn resolve(config: &Path, repo_options: RepoOptions) -> anyhow::Result<()> {
+	let modpack = DrakermoreModConfig::read_from_file(config).await?;
+	let mod_resolver = repo_options.mod_resolver().await?;
+	let mut resolved_mods = ResolvedModList::default();
+	for mod_list_item in modpack.mod_list.iter() {
+		match mod_resolver
+			.resolve(mod_list_item, &modpack.minecraft_version, modpack.loader)
+			.await
+		{
+			Ok(resolved_mod) => resolved_mods.mods.push(resolved_mod),
+			Err(err) => tracing::error!("Couldn't resolve {}: {err}", mod_list_item.id),
+		}
+	}
+	let dependency_requests = resolved_mods
+		.mods
+		.iter()
+		.flat_map(ResolvedMod::dependency_requests)
+		.collect();
+	mod_resolver
+		.add_dependencies(&mut resolved_mods.mods, dependency_requests, &modpack)
+		.await?;
+	for resolved_mod in resolved_mods.mods.iter() {
+		if resolved_mod.needs_manual_download() && resolved_mod.manual_download(&modpack).is_none() {
+			tracing::warn!(
+				"{} can't be downloaded from {:?} by anything but its own launcher, so it needs to be added to \
+				 manual_downloads",
+				resolved_mod.id,
+				resolved_mod.source.repo
+			);
+		}
+	}
+	print!("{}", toml::to_string_pretty(&resolved_mods)?);
+	Ok(())
+}
+
+async fn serve(mut options: ServeOptions) -> anyhow::Result<()> {
+	while options.url_prefix.ends_with('/') {
+		options.url_prefix.pop();
+	}
+
+	let admin_token = match &options.admin_token_file {
+		Some(token_file) => Some(tokio::fs::read_to_string(token_file).await?.trim().to_string()),
+		None => std::env::var("DRAKERMORE_ADMIN_TOKEN").ok(),
+	}
+	.filter(|admin_token| !admin_token.is_empty());
+	let access_secret = match &options.access_secret_file {
+		Some(secret_file) => Some(tokio::fs::read_to_string(secret_file).await?.trim().to_string()),
+		None => std::env::var("DRAKERMORE_ACCESS_SECRET").ok(),
+	}
+	.filter(|access_secret| !access_secret.is_empty());
+
 	// build our application with a route
-	let app = Router::new()
+	let mut app = Router::new()
 		// `GET /` goes to `root`
-		.route("/", get(root))
+		.route("/", get(root));
+	match options.pack_options {
+		PackOptions::Single {
+			config,
+			copy_dir,
+			download_dir,
+			server_mods_dir,
+		} => {
+			let mut pack = Pack::new(config, copy_dir, download_dir, options.url_prefix)?;
+			pack.server_mods_dir = server_mods_dir;
+			pack.admin_token = admin_token;
+			if let Some(access_secret) = access_secret {
+				pack.set_access_secret(access_secret)?;
+			}
+			app = app.merge(pack_router(
+				prepare_pack(pack, options.lockfile_check, options.server_mods_link).await?,
+			));
+		},
+		PackOptions::Multi { server_config } => {
+			let server_config = DrakermoreServerConfig::read_from_file(&server_config).await?;
+			for pack in server_config.into_packs(&options.url_prefix, access_secret.as_deref()) {
+				let (pack_name, mut pack) = pack?;
+				pack.admin_token = admin_token.clone();
+				println!("Preparing pack \"{pack_name}\"...");
+				app = app.nest(
+					&format!("/packs/{pack_name}"),
+					pack_router(prepare_pack(pack, options.lockfile_check, options.server_mods_link).await?),
+				);
+			}
+		},
+	}
+
+	// run our app with hyper, listening globally on port 3000
+	println!("Listening to {}...", options.bind);
+	let listener = tokio::net::TcpListener::bind(&options.bind).await?;
+	axum::serve(listener, app).await?;
+
+	Ok(())
+}
+
+/// Makes sure the pack's config is valid and its mods match its lockfile, links its server mods, generates all its
+/// packwiz files and keeps them up-to-date
+async fn prepare_pack(
+	pack: Pack,
+	lockfile_check: LockfileCheck,
+	server_mods_link: ServerModsLink,
+) -> anyhow::Result<Arc<Pack>> {
+	let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+	check_lockfile(&pack.config, &modpack, &pack.download_dir, lockfile_check).await?;
+	if let Some(server_mods_dir) = &pack.server_mods_dir {
+		let summary = link_server_mods(&pack.download_dir, server_mods_dir, server_mods_link).await?;
+		for drift in summary.drift.iter() {
+			if drift.is_hand_edit() {
+				tracing::warn!("{drift}");
+			} else {
+				tracing::info!("{drift}");
+			}
+		}
+		if let Some(replaced_dir) = summary.replaced_dir {
+			tracing::info!("Moved the previous server mods folder to {}", replaced_dir.display());
+		}
+		println!("Linked {} mods into {}", summary.linked, server_mods_dir.display());
+	}
+	println!("Pre-hashing .jar files...");
+	rebuild_packwiz_cache(&pack).await?;
+	let pack = Arc::new(pack);
+	watch_pack(pack.clone())?;
+	Ok(pack)
+}
+
+fn pack_router(pack: Arc<Pack>) -> Router {
+	let access_secret = pack.access_secret.clone();
+	let admin_router = Router::new()
+		.route(
+			"/admin/jars/:side/:jar_file",
+			put(put_admin_jar).delete(delete_admin_jar),
+		)
+		.route("/admin/jars/:side/:jar_file/move", post(post_admin_move_jar))
+		// Checked before the handlers run, so nobody without the token can make us read an upload's body
+		.route_layer(middleware::from_fn_with_state(pack.clone(), require_admin_token));
+	let router = Router::new()
 		.route("/mmc_pack.zip", get(get_mmc_zip))
-		// `POST /users` goes to `create_user`
+		.route("/modpack.mrpack", get(get_mrpack))
+		.route("/modpack.curseforge.zip", get(get_curseforge_zip))
+		.route("/check", get(get_check))
+		.route("/api/pack", get(get_api_pack))
+		.route("/api/mods", get(get_api_mods))
+		.merge(admin_router)
 		.route("/jars/:side/:jar_file", get(get_mod_jar))
 		.route("/packwiz/pack.toml", get(get_pw_pack))
 		.route("/packwiz/index.toml", get(get_pw_index))
 		.route("/packwiz/mods/:jar_metadata", get(get_pw_mod_metadata))
 		.route("/packwiz/*copy_file_url", get(get_pw_copy_metadata))
-		.nest_service("/copy_files", ServeDir::new(&CLI_OPTIONS.copy_dir));
-
-	// run our app with hyper, listening globally on port 3000
-	println!("Listening to {}...", CLI_OPTIONS.bind);
-	let listener = tokio::net::TcpListener::bind(&CLI_OPTIONS.bind).await?;
-	axum::serve(listener, app).await?;
-
-	Ok(())
+		.route("/copy_files/*copy_file", get(get_copy_file))
+		.with_state(pack);
+	// Without the secret the pack's routes don't exist at all, so they don't give away that there's a pack
+	match access_secret {
+		Some(access_secret) => Router::new().nest(&format!("/{access_secret}"), router),
+		None => router,
+	}
 }
 
 // basic handler that responds with a static string
@@ -97,268 +416,787 @@ async fn root() -> &'static str {
 	"Hello, world! This is drakermore-evolved (or drakermost?)"
 }
 
-async fn pw_mod_metadata_string(realm: PackwizModSide, jar_file_name: PathBuf) -> anyhow::Result<String> {
-	let jar_file_name_str = jar_file_name.to_string_lossy();
-	if !jar_file_name_str.ends_with(".jar") {
-		anyhow::bail!("attempted to show mod metadata for {realm}/{jar_file_name_str} which doesn't end in \".jar\"");
-	}
-	let mut jar_full_path = CLI_OPTIONS.download_dir.canonicalize()?;
-	jar_full_path.push(realm.to_string());
-	// let mut jar_name_path = jar_path.clone();
-	jar_full_path.push(format!("{jar_file_name_str}.name.txt"));
+async fn get_pw_pack(State(pack): State<Arc<Pack>>, request_headers: HeaderMap) -> Response {
+	let pack_file = pack.packwiz.read().unwrap().pack.clone();
+	cached_packwiz_response(&request_headers, pack_file)
+}
+async fn get_pw_index(State(pack): State<Arc<Pack>>, request_headers: HeaderMap) -> Response {
+	let index_file = pack.packwiz.read().unwrap().index.clone();
+	cached_packwiz_response(&request_headers, index_file)
+}
+async fn get_pw_mod_metadata(
+	State(pack): State<Arc<Pack>>,
+	request_headers: HeaderMap,
+	AxumPath(jar_metadata_name): AxumPath<String>,
+) -> Response {
+	ok_or_anyhow_response((|| {
+		let jar_metadata_name = safe_relative_path(&jar_metadata_name)?;
+		let metadata_file = jar_metadata_name
+			.to_str()
+			.and_then(|jar_metadata_name| jar_metadata_name.strip_suffix(".pw.toml"))
+			.and_then(|jar_file_stem| {
+				let packwiz = pack.packwiz.read().unwrap();
+				packwiz
+					.mods
+					.get(&format!("{jar_file_stem}.jar"))
+					.map(|cached_mod| cached_mod.metadata.clone())
+			})
+			.ok_or_else(|| SafePathError::NotFound(jar_metadata_name.to_string_lossy().into()))?;
+		Ok(cached_packwiz_response(&request_headers, metadata_file))
+	})())
+}
+async fn get_pw_copy_metadata(
+	State(pack): State<Arc<Pack>>,
+	request_headers: HeaderMap,
+	AxumPath(copy_metadata_name): AxumPath<String>,
+) -> Response {
+	ok_or_anyhow_response((|| {
+		let copy_metadata_name = safe_relative_path(&copy_metadata_name)?;
+		let metadata_file = copy_metadata_name
+			.to_str()
+			.and_then(|copy_metadata_name| copy_metadata_name.strip_suffix(".pw.toml"))
+			.and_then(|copy_file_name| {
+				let packwiz = pack.packwiz.read().unwrap();
+				packwiz
+					.copy_files
+					.get(Path::new(copy_file_name))
+					.map(|cached_file| cached_file.metadata.clone())
+			})
+			.ok_or_else(|| SafePathError::NotFound(copy_metadata_name.to_string_lossy().into()))?;
+		Ok(cached_packwiz_response(&request_headers, metadata_file))
+	})())
+}
+fn cached_packwiz_response(request_headers: &HeaderMap, packwiz_file: CachedPackwizFile) -> Response {
+	conditional_response(
+		request_headers,
+		&packwiz_file.sha512,
+		packwiz_file.last_modified,
+		packwiz_file.contents.to_string(),
+	)
+}
 
-	let mod_name = fs::read_to_string(&jar_full_path)
-		.await
-		.map(|mut string| {
-			string.truncate(string.trim_end().len());
-			string
-		})
-		.unwrap_or(jar_file_name_str[0..(jar_file_name_str.len() - 4)].into());
+async fn get_mmc_zip(State(pack): State<Arc<Pack>>) -> Response {
+	ok_or_anyhow_response(
+		async {
+			let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
 
-	jar_full_path.pop();
-	jar_full_path.push(&jar_file_name);
+			let url_prefix = pack.url_prefix.clone();
+			Ok(ZipResponse::new(format!("{}.zip", modpack.name), move |zip| {
+				let zip_options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
 
-	Ok(toml::to_string_pretty(&PackwizMod {
-		download: PackwizModDownload {
-			url: format!("{}/jars/{realm}/{jar_file_name_str}", &CLI_OPTIONS.url_prefix).into(),
-			hash_format: PackwizHashFormat::Sha512,
-			hash: Cow::Borrowed(&get_hash_from_file(&jar_full_path).await?),
-		},
-		name: &mod_name,
-		filename: jar_file_name_str,
-		side: realm,
-	})?)
-}
-async fn pw_copy_metadata_string(full_file_path: &Path) -> anyhow::Result<String> {
-	let file_name = full_file_path.file_name().unwrap_or_default().to_string_lossy();
-	let file_path = full_file_path.strip_prefix(&CLI_OPTIONS.copy_dir)?;
-	let file_path_str = file_path.to_string_lossy();
-
-	Ok(toml::to_string_pretty(&PackwizMod {
-		download: PackwizModDownload {
-			url: format!("{}/copy_files/{file_path_str}", &CLI_OPTIONS.url_prefix).into(),
-			hash_format: PackwizHashFormat::Sha512,
-			hash: Cow::Borrowed(&get_hash_from_file(&full_file_path).await?),
-		},
-		name: &file_name,
-		filename: file_name.clone(),
-		side: PackwizModSide::Client,
-	})?)
-}
-
-async fn pw_index_string() -> anyhow::Result<String> {
-	let mut jar_full_path = CLI_OPTIONS.download_dir.canonicalize()?;
-	let mut result: Vec<PackwizIndexFile<'_>> = Vec::new();
-	for realm in PackwizModSide::all() {
-		jar_full_path.push(realm.to_string());
-		let mut dir_reader = fs::read_dir(&jar_full_path).await?;
-		while let Some(dir_entry) = dir_reader.next_entry().await? {
-			let jar_file_name = dir_entry.file_name();
-			let jar_file_name_str = jar_file_name.to_string_lossy();
-			if !jar_file_name_str.ends_with(".jar") || !dir_entry.file_type().await?.is_file() {
-				continue;
-			}
-			let jar_file_name_str = &jar_file_name_str[0..(jar_file_name_str.len() - 4)];
-			result.push(PackwizIndexFile {
-				file: format!("mods/{jar_file_name_str}.pw.toml").into(),
-				hash: hex::encode(Sha512::digest(
-					pw_mod_metadata_string(realm, jar_file_name.into()).await?,
-				))
-				.into(),
-				metafile: true,
-			});
+				zip.start_file("mmc-pack.json", zip_options)?;
+				zip.write_all(&serde_json::to_vec(&MmcPack {
+					components: &modpack.mmc_pack_components,
+					..Default::default()
+				})?)?;
+				zip.start_file("instance.cfg", zip_options)?;
+				zip.write_all(
+					format!(
+						"InstanceType=OneSix
+OverrideCommands=true
+PreLaunchCommand=\"$INST_JAVA\" -jar packwiz-installer-bootstrap.jar {}/packwiz/pack.toml
+name=${}
+",
+						url_prefix, modpack.name
+					)
+					.as_bytes(),
+				)?;
+
+				zip.start_file(
+					".minecraft/packwiz-installer-bootstrap.jar",
+					// .jar files are already zipped
+					SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+				)?;
+				zip.write_all(PACKWIZ_INSTALLER_BOOTSTRAP_JAR)?;
+
+				zip.start_file(
+					".minecraft/servers.dat",
+					// .jar files are already zipped
+					SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+				)?;
+				zip.write_all(
+					&Nbt::new(
+						"".into(),
+						NbtCompound::from_iter([(
+							"servers".to_owned(),
+							NbtTag::List(
+								modpack
+									.minecraft_servers
+									.into_iter()
+									.map(|server| {
+										NbtTag::Compound(NbtCompound::from_iter([
+											("name".to_owned(), NbtTag::String(server.name)),
+											("ip".to_owned(), NbtTag::String(server.ip)),
+											("hidden".to_owned(), NbtTag::Byte(0)),
+										]))
+									})
+									.collect::<Vec<_>>(),
+							),
+						)]),
+					)
+					.write(),
+				)?;
+
+				Ok(())
+			}))
 		}
-		jar_full_path.pop();
-	}
-	let mut copy_files = subfiles_in_folder(CLI_OPTIONS.copy_dir.clone(), true);
-	while let Some(full_file_path) = copy_files.next().await {
-		let full_file_path = full_file_path?;
-		let relative_file_path = full_file_path.strip_prefix(CLI_OPTIONS.copy_dir.clone())?;
-		result.push(PackwizIndexFile {
-			file: format!("{}.pw.toml", relative_file_path.to_string_lossy()).into(),
-			hash: hex::encode(Sha512::digest(
-				pw_copy_metadata_string(&full_file_path).await?,
-			))
-			.into(),
-			metafile: true,
-		});
-	}
-	Ok(toml::to_string_pretty(&PackwizIndex {
-		hash_format: PackwizHashFormat::Sha512,
-		files: result,
-	})?)
+		.await,
+	)
 }
 
-async fn get_pw_pack() -> Response {
+async fn get_mrpack(State(pack): State<Arc<Pack>>) -> Response {
 	ok_or_anyhow_response(
 		async {
-			let modpack: DrakermoreModConfig =
-				toml::from_str(&String::from_utf8(fs::read(&CLI_OPTIONS.config).await?)?)?;
-
-			Ok(toml::to_string_pretty(&PackwizMetadata {
-				name: modpack.name.into(),
-				author: modpack.pack_author.into(),
-				version: modpack.pack_version.into(),
-				pack_format: PackwizFormatVersion::V1_1_0,
-				versions: PackwizMetadataVersions {
-					minecraft: modpack.minecraft_version.into(),
-					fabric: modpack.fabric_loader_version.into(),
-				},
-				index: PackwizMetadataIndex {
-					file: "index.toml".into(),
-					hash_format: PackwizHashFormat::Sha512,
-					hash: hex::encode(Sha512::digest(pw_index_string().await?)).into(),
-				},
-			})?)
+			let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+			let exported_files = ExportedFiles::from_pack(&pack).await?;
+			Ok(ZipResponse::new(format!("{}.mrpack", modpack.name), move |zip| {
+				write_mrpack(zip, &modpack, &exported_files)
+			})
+			.with_content_type("application/x-modrinth-modpack+zip"))
 		}
 		.await,
 	)
 }
-async fn get_pw_index() -> Response {
-	ok_or_anyhow_response(pw_index_string().await)
+
+async fn get_curseforge_zip(State(pack): State<Arc<Pack>>) -> Response {
+	ok_or_anyhow_response(
+		async {
+			let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+			let exported_files = ExportedFiles::from_pack(&pack).await?;
+			Ok(ZipResponse::new(
+				format!("{}.curseforge.zip", modpack.name),
+				move |zip| write_curseforge_zip(zip, &modpack, &exported_files),
+			))
+		}
+		.await,
+	)
 }
-async fn find_jar_realm(jar_file_name: &Path) -> anyhow::Result<Option<PackwizModSide>> {
-	let mut jar_full_path = CLI_OPTIONS.download_dir.canonicalize()?;
-	for realm in PackwizModSide::all() {
-		jar_full_path.push(realm.to_string());
-		jar_full_path.push(jar_file_name);
-		if fs::try_exists(&jar_full_path).await? {
-			return Ok(Some(realm));
+
+async fn get_api_pack(State(pack): State<Arc<Pack>>, Query(filter): Query<ApiModFilter>) -> Response {
+	ok_or_anyhow_response(
+		async {
+			let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+			let exported_files = ExportedFiles::from_pack(&pack).await?;
+			Ok(Json(api_pack(&pack, modpack, &exported_files, &filter)))
+		}
+		.await,
+	)
+}
+async fn get_api_mods(State(pack): State<Arc<Pack>>, Query(filter): Query<ApiModFilter>) -> Response {
+	ok_or_anyhow_response(
+		async {
+			let exported_files = ExportedFiles::from_pack(&pack).await?;
+			Ok(Json(api_mods(&exported_files, &filter)))
 		}
-		jar_full_path.pop();
-		jar_full_path.pop();
+		.await,
+	)
+}
+async fn require_admin_token(State(pack): State<Arc<Pack>>, request: Request, next: Next) -> Response {
+	match check_admin_token(&pack, request.headers()) {
+		Ok(()) => next.run(request).await,
+		Err(err) => ok_or_anyhow_response::<Response>(Err(err.into())),
 	}
-	Ok(None)
 }
-async fn get_pw_mod_metadata(AxumPath(mut jar_file_name_str): AxumPath<String>) -> Response {
+#[derive(Debug, serde::Deserialize)]
+struct AdminUploadQuery {
+	/// Written to the jar's .name.txt file
+	name: Option<String>,
+}
+async fn put_admin_jar(
+	State(pack): State<Arc<Pack>>,
+	AxumPath((realm, jar_file_name)): AxumPath<(PackwizModSide, String)>,
+	Query(query): Query<AdminUploadQuery>,
+	body: Body,
+) -> Response {
 	ok_or_anyhow_response(
 		async {
-			if !jar_file_name_str.ends_with(".pw.toml") {
-				return Err(IoError::new(
-					IoErrorKind::NotFound,
-					format!("cannot find {jar_file_name_str} in this folder"),
-				)
-				.into());
-			}
-			jar_file_name_str.truncate(jar_file_name_str.len() - "pw.toml".len());
-			jar_file_name_str.push_str("jar");
-			let jar_file_name = PathBuf::from(jar_file_name_str.clone());
-			Ok(pw_mod_metadata_string(
-				find_jar_realm(&jar_file_name)
-					.await?
-					.ok_or_else(|| IoError::new(IoErrorKind::NotFound, format!("Cannot find {jar_file_name_str}")))?,
-				jar_file_name,
-			)
-			.await?)
+			let uploaded_jar = upload_jar(&pack, realm, &jar_file_name, query.name.as_deref(), body).await?;
+			Ok((StatusCode::CREATED, Json(uploaded_jar)))
 		}
 		.await,
 	)
 }
-async fn get_pw_copy_metadata(AxumPath(mut copy_file_name_str): AxumPath<String>) -> Response {
+#[derive(Debug, serde::Deserialize)]
+struct AdminMoveQuery {
+	to: PackwizModSide,
+}
+async fn post_admin_move_jar(
+	State(pack): State<Arc<Pack>>,
+	AxumPath((realm, jar_file_name)): AxumPath<(PackwizModSide, String)>,
+	Query(query): Query<AdminMoveQuery>,
+) -> Response {
+	ok_or_anyhow_response(async { Ok(Json(move_jar(&pack, realm, &jar_file_name, query.to).await?)) }.await)
+}
+async fn delete_admin_jar(
+	State(pack): State<Arc<Pack>>,
+	AxumPath((realm, jar_file_name)): AxumPath<(PackwizModSide, String)>,
+) -> Response {
 	ok_or_anyhow_response(
 		async {
-			if !copy_file_name_str.ends_with(".pw.toml") {
-				return Err(IoError::new(
-					IoErrorKind::NotFound,
-					format!("cannot find {copy_file_name_str} in this folder"),
-				)
-				.into());
-			}
-			copy_file_name_str.truncate(copy_file_name_str.len() - "pw.toml".len());
-			let copy_file_name = PathBuf::from(copy_file_name_str.clone());
-			let full_path = CLI_OPTIONS.config.join(copy_file_name);
-			Ok(pw_copy_metadata_string(&full_path).await?)
+			delete_jar(&pack, realm, &jar_file_name).await?;
+			Ok(StatusCode::NO_CONTENT.into_response())
 		}
 		.await,
 	)
 }
-
-async fn get_mmc_zip() -> Response {
+async fn get_check(State(pack): State<Arc<Pack>>) -> Response {
 	ok_or_anyhow_response(
 		async {
-			let modpack: DrakermoreModConfig =
-				toml::from_str(&String::from_utf8(fs::read(&CLI_OPTIONS.config).await?)?)?;
-
-			let zip_options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-
-			let mut response = ZipResponse::new(format!("{}.zip", modpack.name));
-			response.start_file("mmc-pack.json", zip_options.clone())?;
-			response.write(&serde_json::to_vec(&MmcPack {
-				components: &modpack.mmc_pack_components,
-				..Default::default()
-			})?)?;
-			response.start_file("instance.cfg", zip_options.clone())?;
-			response.write(
-				format!(
-					"InstanceType=OneSix
-OverrideCommands=true
-PreLaunchCommand=\"$INST_JAVA\" -jar packwiz-installer-bootstrap.jar {}/packwiz/pack.toml
-name=${}
-",
-					CLI_OPTIONS.url_prefix, modpack.name
-				)
-				.as_bytes(),
-			)?;
-
-			response.start_file(
-				".minecraft/packwiz-installer-bootstrap.jar",
-				// .jar files are already zipped
-				SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
-			)?;
-			response.write(PACKWIZ_INSTALLER_BOOTSTRAP_JAR)?;
-
-			response.start_file(
-				".minecraft/servers.dat",
-				// .jar files are already zipped
-				SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
-			)?;
-			response.write(
-				&Nbt::new(
-					"".into(),
-					NbtCompound::from_iter([(
-						"servers".to_owned(),
-						NbtTag::List(
-							modpack
-								.minecraft_servers
-								.into_iter()
-								.map(|server| {
-									NbtTag::Compound(NbtCompound::from_iter([
-										("name".to_owned(), NbtTag::String(server.name)),
-										("ip".to_owned(), NbtTag::String(server.ip)),
-										("hidden".to_owned(), NbtTag::Byte(0)),
-									]))
-								})
-								.collect::<Vec<_>>(),
-						),
-					)]),
-				)
-				.write(),
-			)?;
+			let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+			Ok(Json(check_jars(&modpack, &pack_jars(&pack).await?)))
+		}
+		.await,
+	)
+}
 
+async fn get_mod_jar(
+	State(pack): State<Arc<Pack>>,
+	AxumPath((realm, jar_file_name)): AxumPath<(PackwizModSide, String)>,
+	request: Request,
+) -> Response {
+	ok_or_anyhow_response(
+		async {
+			let jar_path = resolve_safe_path(&pack.download_dir.join(realm.to_string()), &jar_file_name).await?;
+			let mut response = serve_file(ServeFile::new_with_mime(&jar_path, &JAVA_ARCHIVE_MIME), request).await;
+			if response.status().is_success() {
+				let (header_name, header_value) =
+					download_file_name_header(&jar_path.file_name().unwrap_or_default().to_string_lossy());
+				response.headers_mut().insert(header_name, header_value);
+			}
 			Ok(response)
 		}
 		.await,
 	)
 }
-
-async fn get_mod_jar(AxumPath((realm, jar_file_name)): AxumPath<(PackwizModSide, PathBuf)>) -> Response {
+async fn get_copy_file(
+	State(pack): State<Arc<Pack>>,
+	AxumPath(copy_file_name): AxumPath<String>,
+	request: Request,
+) -> Response {
 	ok_or_anyhow_response(
 		async {
-			let mut jar_path = CLI_OPTIONS.download_dir.canonicalize()?;
-			jar_path.push(realm.to_string());
-			jar_path.push(&jar_file_name);
-			Ok((
-				[
+			let copy_file_path = resolve_safe_path(&pack.copy_dir, &copy_file_name).await?;
+			Ok(serve_file(ServeFile::new(copy_file_path), request).await)
+		}
+		.await,
+	)
+}
+/// ServeFile streams the file from disk and takes care of HEAD, Range and conditional requests for us
+async fn serve_file(serve_file: ServeFile, request: Request) -> Response {
+	let Ok(response) = serve_file.oneshot(request).await;
+	response.map(Body::new)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, io::Read, os::unix::fs::symlink};
+
+	use axum::http::{header, StatusCode};
+	use bytes::Bytes;
+	use sha1::Sha1;
+	use sha2::{Digest, Sha512};
+	use tempfile::TempDir;
+
+	use super::*;
+	use crate::test_utils::{test_config_toml, test_pack};
+
+	async fn test_pack_router() -> (TempDir, Router) {
+		test_pack_router_with(None, &[]).await
+	}
+
+	/// Creates a pack with a secret file next to it, and symlinks inside the pack which point to the secret. The extra
+	/// files are written relative to the pack's root before the pack is set up, so they can replace the defaults.
+	async fn test_pack_router_with(access_secret: Option<&str>, extra_files: &[(&str, &str)]) -> (TempDir, Router) {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let root = temp_dir.path();
+		let mut pack = test_pack(root, "");
+		fs::write(root.join("secret.txt"), "hunter2").unwrap();
+		fs::write(root.join("download/both/good.jar"), "not really a jar").unwrap();
+		symlink(root.join("secret.txt"), root.join("download/both/escape.jar")).unwrap();
+		fs::create_dir_all(root.join("copy/config")).unwrap();
+		fs::write(root.join("copy/config/foo.json"), "{}").unwrap();
+		symlink(root.join("secret.txt"), root.join("copy/escape.txt")).unwrap();
+		for (path, contents) in extra_files {
+			fs::create_dir_all(root.join(path).parent().unwrap()).unwrap();
+			fs::write(root.join(path), contents).unwrap();
+		}
+
+		pack.admin_token = Some("hunter3".into());
+		if let Some(access_secret) = access_secret {
+			pack.set_access_secret(access_secret.into()).unwrap();
+		}
+		let router = pack_router(
+			prepare_pack(pack, LockfileCheck::Refuse, ServerModsLink::Symlink)
+				.await
+				.unwrap(),
+		);
+		(temp_dir, router)
+	}
+
+	async fn get_status(router: &Router, uri: &str) -> StatusCode {
+		router
+			.clone()
+			.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+			.await
+			.unwrap()
+			.status()
+	}
+
+	async fn assert_statuses(router: &Router, expected: &[(&str, StatusCode)]) {
+		for (uri, expected_status) in expected {
+			assert_eq!(get_status(router, uri).await, *expected_status, "GET {uri}");
+		}
+	}
+
+	#[tokio::test]
+	async fn jar_route_rejects_traversal() {
+		let (_temp_dir, router) = test_pack_router().await;
+		assert_statuses(
+			&router,
+			&[
+				("/jars/both/good.jar", StatusCode::OK),
+				("/jars/both/missing.jar", StatusCode::NOT_FOUND),
+				("/jars/both/..%2F..%2Fsecret.txt", StatusCode::FORBIDDEN),
+				("/jars/both/%2Fetc%2Fpasswd", StatusCode::FORBIDDEN),
+				("/jars/both/escape.jar", StatusCode::FORBIDDEN),
+			],
+		)
+		.await;
+	}
+
+	#[tokio::test]
+	async fn copy_file_route_rejects_traversal() {
+		let (_temp_dir, router) = test_pack_router().await;
+		assert_statuses(
+			&router,
+			&[
+				("/copy_files/config/foo.json", StatusCode::OK),
+				("/copy_files/config/missing.json", StatusCode::NOT_FOUND),
+				("/copy_files/config", StatusCode::NOT_FOUND),
+				("/copy_files/../secret.txt", StatusCode::FORBIDDEN),
+				("/copy_files/config/..%2F..%2Fsecret.txt", StatusCode::FORBIDDEN),
+				("/copy_files/%2Fetc%2Fpasswd", StatusCode::FORBIDDEN),
+				("/copy_files/escape.txt", StatusCode::FORBIDDEN),
+			],
+		)
+		.await;
+	}
+
+	#[tokio::test]
+	async fn metadata_routes_reject_traversal() {
+		let (_temp_dir, router) = test_pack_router().await;
+		assert_statuses(
+			&router,
+			&[
+				("/packwiz/mods/good.pw.toml", StatusCode::OK),
+				("/packwiz/config/foo.json.pw.toml", StatusCode::OK),
+				("/packwiz/mods/..%2F..%2Fsecret.pw.toml", StatusCode::FORBIDDEN),
+				("/packwiz/../secret.txt.pw.toml", StatusCode::FORBIDDEN),
+				("/packwiz/config/..%2F..%2Fsecret.txt.pw.toml", StatusCode::FORBIDDEN),
+				("/packwiz/%2Fetc%2Fpasswd.pw.toml", StatusCode::FORBIDDEN),
+				("/packwiz/mods/escape.pw.toml", StatusCode::NOT_FOUND),
+				("/packwiz/escape.txt.pw.toml", StatusCode::NOT_FOUND),
+			],
+		)
+		.await;
+	}
+
+	#[tokio::test]
+	async fn api_mods_can_be_filtered() {
+		let (_temp_dir, router) = test_pack_router().await;
+		for (uri, expected_file_names) in [
+			("/api/mods", &["good.jar"][..]),
+			("/api/mods?side=server&search=GOOD", &["good.jar"]),
+			("/api/mods?search=sodium", &[]),
+			("/api/mods?repo=modrinth", &[]),
+		] {
+			let response = router
+				.clone()
+				.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+				.await
+				.unwrap();
+			assert_eq!(response.status(), StatusCode::OK, "GET {uri}");
+			let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+			let mods: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+			let file_names: Vec<_> = mods
+				.iter()
+				.map(|api_mod| api_mod["file_name"].as_str().unwrap())
+				.collect();
+			assert_eq!(file_names, expected_file_names, "GET {uri}");
+		}
+		assert_eq!(
+			get_status(&router, "/api/mods?side=nowhere").await,
+			StatusCode::BAD_REQUEST
+		);
+		assert_eq!(get_status(&router, "/api/pack").await, StatusCode::OK);
+	}
+
+	async fn admin_request(router: &Router, method: &str, uri: &str, token: &str, body: Vec<u8>) -> StatusCode {
+		router
+			.clone()
+			.oneshot(
+				Request::builder()
+					.method(method)
+					.uri(uri)
+					.header("Authorization", format!("Bearer {token}"))
+					.body(Body::from(body))
+					.unwrap(),
+			)
+			.await
+			.unwrap()
+			.status()
+	}
+
+	#[tokio::test]
+	async fn admin_can_upload_move_and_delete_jars() {
+		let (temp_dir, router) = test_pack_router().await;
+		let mut jar = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+		jar.start_file("hello.txt", SimpleFileOptions::default()).unwrap();
+		jar.write_all(b"hello").unwrap();
+		let jar = jar.finish().unwrap().into_inner();
+
+		for (method, uri, token, body, expected_status) in [
+			(
+				"PUT",
+				"/admin/jars/server/new.jar",
+				"wrong",
+				jar.clone(),
+				StatusCode::UNAUTHORIZED,
+			),
+			(
+				"PUT",
+				"/admin/jars/server/new.jar",
+				"hunter3",
+				b"not a jar".to_vec(),
+				StatusCode::BAD_REQUEST,
+			),
+			(
+				"PUT",
+				"/admin/jars/server/new.txt",
+				"hunter3",
+				jar.clone(),
+				StatusCode::BAD_REQUEST,
+			),
+			(
+				"PUT",
+				"/admin/jars/server/..%2Fnew.jar",
+				"hunter3",
+				jar.clone(),
+				StatusCode::FORBIDDEN,
+			),
+			(
+				"PUT",
+				"/admin/jars/server/good.jar",
+				"hunter3",
+				jar.clone(),
+				StatusCode::CONFLICT,
+			),
+			(
+				"PUT",
+				"/admin/jars/server/new.jar?name=New%20Mod",
+				"hunter3",
+				jar.clone(),
+				StatusCode::CREATED,
+			),
+		] {
+			assert_eq!(
+				admin_request(&router, method, uri, token, body).await,
+				expected_status,
+				"{method} {uri}"
+			);
+		}
+		let metadata = temp_dir.path().join("download/server/new.jar.name.txt");
+		assert_eq!(fs::read_to_string(&metadata).unwrap(), "New Mod\n");
+		// The index is updated before the response is sent, without waiting for the file watcher
+		assert_eq!(get_status(&router, "/packwiz/mods/new.pw.toml").await, StatusCode::OK);
+
+		// A replaced jar's name and option don't carry over to the jar which replaces it
+		let option = temp_dir.path().join("download/server/new.jar.option.toml");
+		fs::write(&option, "optional = true\n").unwrap();
+		let status = admin_request(&router, "PUT", "/admin/jars/server/new.jar", "hunter3", jar.clone()).await;
+		assert_eq!(status, StatusCode::CREATED);
+		assert!(!metadata.exists());
+		assert!(!option.exists());
+		let pw_toml = get_body(&router, "/packwiz/mods/new.pw.toml").await;
+		assert!(!String::from_utf8_lossy(&pw_toml).contains("[option]"));
+		let status = admin_request(
+			&router,
+			"PUT",
+			"/admin/jars/server/new.jar?name=Newer%20Mod",
+			"hunter3",
+			jar.clone(),
+		)
+		.await;
+		assert_eq!(status, StatusCode::CREATED);
+		assert_eq!(fs::read_to_string(&metadata).unwrap(), "Newer Mod\n");
+
+		let status = admin_request(
+			&router,
+			"POST",
+			"/admin/jars/server/new.jar/move?to=client",
+			"hunter3",
+			vec![],
+		)
+		.await;
+		assert_eq!(status, StatusCode::OK);
+		assert!(temp_dir.path().join("download/client/new.jar.name.txt").exists());
+		assert_eq!(get_status(&router, "/jars/client/new.jar").await, StatusCode::OK);
+
+		let status = admin_request(&router, "DELETE", "/admin/jars/client/new.jar", "hunter3", vec![]).await;
+		assert_eq!(status, StatusCode::NO_CONTENT);
+		assert_eq!(
+			get_status(&router, "/packwiz/mods/new.pw.toml").await,
+			StatusCode::NOT_FOUND
+		);
+		let status = admin_request(&router, "DELETE", "/admin/jars/client/new.jar", "hunter3", vec![]).await;
+		assert_eq!(status, StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn admin_token_is_checked_before_the_upload_is_read() {
+		let (_temp_dir, router) = test_pack_router().await;
+		// A body which never ends, so reading any of it would hang
+		let endless_body = Body::from_stream(futures::stream::pending::<Result<Bytes, std::io::Error>>());
+		let response = tokio::time::timeout(
+			std::time::Duration::from_secs(5),
+			router.oneshot(
+				Request::builder()
+					.method("PUT")
+					.uri("/admin/jars/server/new.jar")
+					.header("Authorization", "Bearer wrong")
+					.body(endless_body)
+					.unwrap(),
+			),
+		)
+		.await
+		.expect("the body shouldn't be read without the token")
+		.unwrap();
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+	}
+
+	async fn get_body(router: &Router, uri: &str) -> Bytes {
+		let response = router
+			.clone()
+			.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::OK, "GET {uri}");
+		axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+	}
+
+	#[tokio::test]
+	async fn access_secret_is_needed_and_carried_into_generated_urls() {
+		let (_temp_dir, router) = test_pack_router_with(Some("s3cr3t"), &[]).await;
+		assert_statuses(
+			&router,
+			&[
+				("/packwiz/pack.toml", StatusCode::NOT_FOUND),
+				("/jars/both/good.jar", StatusCode::NOT_FOUND),
+				("/copy_files/config/foo.json", StatusCode::NOT_FOUND),
+				("/mmc_pack.zip", StatusCode::NOT_FOUND),
+				("/wrong/packwiz/pack.toml", StatusCode::NOT_FOUND),
+				("/s3cr3t/packwiz/pack.toml", StatusCode::OK),
+				("/s3cr3t/jars/both/good.jar", StatusCode::OK),
+				("/s3cr3t/copy_files/config/foo.json", StatusCode::OK),
+			],
+		)
+		.await;
+
+		let metadata = get_body(&router, "/s3cr3t/packwiz/mods/good.pw.toml").await;
+		assert!(String::from_utf8_lossy(&metadata).contains("\"http://localhost/s3cr3t/jars/both/good.jar\""));
+		let mmc_zip = get_body(&router, "/s3cr3t/mmc_pack.zip").await;
+		let mut instance_cfg = String::new();
+		zip::ZipArchive::new(std::io::Cursor::new(mmc_zip))
+			.unwrap()
+			.by_name("instance.cfg")
+			.unwrap()
+			.read_to_string(&mut instance_cfg)
+			.unwrap();
+		assert!(instance_cfg.contains("packwiz-installer-bootstrap.jar http://localhost/s3cr3t/packwiz/pack.toml\n"));
+	}
+
+	fn read_zip_entry(zip_bytes: &Bytes, name: &str) -> String {
+		let mut contents = String::new();
+		zip::ZipArchive::new(std::io::Cursor::new(zip_bytes.clone()))
+			.unwrap()
+			.by_name(name)
+			.unwrap()
+			.read_to_string(&mut contents)
+			.unwrap();
+		contents
+	}
+
+	#[tokio::test]
+	async fn mrpack_lists_mods_by_side_and_bundles_copy_files_as_overrides() {
+		let (_temp_dir, router) = test_pack_router_with(
+			None,
+			&[
+				("download/client/sodium.jar", "client jar"),
+				("download/client/sodium.jar.option.toml", "optional = true\n"),
+				("download/server/ledger.jar", "server jar"),
+				("copy/both/options.txt", "fov:1.0"),
+				("copy/server/server.properties", "motd=hi"),
+			],
+		)
+		.await;
+		let mrpack = get_body(&router, "/modpack.mrpack").await;
+		let index: serde_json::Value = serde_json::from_str(&read_zip_entry(&mrpack, "modrinth.index.json")).unwrap();
+		assert_eq!(index["formatVersion"], 1);
+		assert_eq!(index["versionId"], "1.0.0");
+		assert_eq!(
+			index["dependencies"],
+			serde_json::json!({ "minecraft": "1.20.1", "fabric-loader": "0.16.5" })
+		);
+		let mut files: Vec<_> = index["files"].as_array().unwrap().iter().collect();
+		files.sort_by_key(|file| file["path"].as_str().unwrap().to_string());
+		let summary: Vec<_> = files
+			.iter()
+			.map(|file| {
+				(
+					file["path"].as_str().unwrap(),
+					file["env"]["client"].as_str().unwrap(),
+					file["env"]["server"].as_str().unwrap(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			summary,
+			[
+				("mods/good.jar", "required", "required"),
+				("mods/ledger.jar", "unsupported", "required"),
+				("mods/sodium.jar", "optional", "unsupported"),
+			]
+		);
+		let good_jar = b"not really a jar";
+		assert_eq!(files[0]["hashes"]["sha1"], hex::encode(Sha1::digest(good_jar)));
+		assert_eq!(files[0]["hashes"]["sha512"], hex::encode(Sha512::digest(good_jar)));
+		assert_eq!(files[0]["fileSize"], good_jar.len());
+		assert_eq!(
+			files[0]["downloads"],
+			serde_json::json!(["http://localhost/jars/both/good.jar"])
+		);
+
+		assert_eq!(read_zip_entry(&mrpack, "overrides/options.txt"), "fov:1.0");
+		// Files outside of a side folder only go to the client
+		assert_eq!(read_zip_entry(&mrpack, "client-overrides/config/foo.json"), "{}");
+		assert_eq!(read_zip_entry(&mrpack, "server-overrides/server.properties"), "motd=hi");
+	}
+	#[tokio::test]
+	async fn curseforge_zip_lists_curseforge_mods_and_bundles_the_rest_for_the_client() {
+		for (loader, minecraft_version, loader_version, expected_loader_id) in [
+			("quilt", "1.20.1", "0.26.4", "quilt-0.26.4"),
+			("neoforge", "1.21.1", "21.1.72", "neoforge-21.1.72"),
+		] {
+			let config = test_config_toml(&format!(
+				r#"
+loader = "{loader}"
+minecraft_version = "{minecraft_version}"
+loader_version = "{loader_version}"
+"#
+			));
+			let (_temp_dir, router) = test_pack_router_with(
+				None,
+				&[
+					("mod-list.toml", &config),
+					("download/client/sodium.jar", "client jar"),
 					(
-						header::CONTENT_TYPE,
-						HeaderValue::from_static("application/java-archive"),
+						"download/client/sodium.jar.source.toml",
+						"repo = \"curseforge\"\nproject_id = \"394468\"\nversion_id = \"5146090\"\n",
 					),
-					download_file_name_header(&jar_file_name.as_os_str().to_string_lossy()),
+					("download/client/sodium.jar.option.toml", "optional = true\n"),
+					("download/server/ledger.jar", "server jar"),
+					("copy/both/options.txt", "fov:1.0"),
+					("copy/server/server.properties", "motd=hi"),
 				],
-				// TODO: Do we have to buffer the entire file?
-				Bytes::from(fs::read(jar_path).await?),
-			))
+			)
+			.await;
+			let curseforge_zip = get_body(&router, "/modpack.curseforge.zip").await;
+			let manifest: serde_json::Value =
+				serde_json::from_str(&read_zip_entry(&curseforge_zip, "manifest.json")).unwrap();
+			assert_eq!(
+				manifest["minecraft"],
+				serde_json::json!({
+					"version": minecraft_version,
+					"modLoaders": [{ "id": expected_loader_id, "primary": true }],
+				}),
+				"{loader}"
+			);
+			assert_eq!(manifest["manifestType"], "minecraftModpack");
+			assert_eq!(manifest["manifestVersion"], 1);
+			assert_eq!(manifest["version"], "1.0.0");
+			assert_eq!(manifest["author"], "Tester");
+			assert_eq!(manifest["overrides"], "overrides");
+			assert_eq!(
+				manifest["files"],
+				serde_json::json!([{ "projectID": 394468, "fileID": 5146090, "required": false }])
+			);
+
+			let mut file_names: Vec<_> = zip::ZipArchive::new(std::io::Cursor::new(curseforge_zip.clone()))
+				.unwrap()
+				.file_names()
+				.map(String::from)
+				.collect();
+			file_names.sort();
+			// Server-only jars and files are left out, since the CurseForge app only sets up clients
+			assert_eq!(
+				file_names,
+				[
+					"manifest.json",
+					"overrides/config/foo.json",
+					"overrides/mods/good.jar",
+					"overrides/options.txt",
+				]
+			);
+			assert_eq!(
+				read_zip_entry(&curseforge_zip, "overrides/mods/good.jar"),
+				"not really a jar"
+			);
+			assert_eq!(read_zip_entry(&curseforge_zip, "overrides/options.txt"), "fov:1.0");
 		}
-		.await,
-	)
+	}
+	#[tokio::test]
+	async fn jars_are_served_with_ranges_and_head_requests() {
+		let (_temp_dir, router) = test_pack_router().await;
+		let response = router
+			.clone()
+			.oneshot(
+				Request::builder()
+					.uri("/jars/both/good.jar")
+					.header(header::RANGE, "bytes=4-9")
+					.body(Body::empty())
+					.unwrap(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+		assert_eq!(response.headers()[header::CONTENT_TYPE], "application/java-archive");
+		assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 4-9/16");
+		assert_eq!(
+			response.headers()[header::CONTENT_DISPOSITION],
+			"attachment; filename=\"good.jar\"; filename*=utf-8''good.jar"
+		);
+		assert_eq!(
+			axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(),
+			"really"
+		);
+
+		let response = router
+			.clone()
+			.oneshot(
+				Request::builder()
+					.method("HEAD")
+					.uri("/jars/both/good.jar")
+					.body(Body::empty())
+					.unwrap(),
+			)
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.headers()[header::CONTENT_TYPE], "application/java-archive");
+		assert_eq!(response.headers()[header::CONTENT_LENGTH], "16");
+		assert!(axum::body::to_bytes(response.into_body(), usize::MAX)
+			.await
+			.unwrap()
+			.is_empty());
+	}
 }
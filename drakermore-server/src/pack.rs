@@ -0,0 +1,125 @@
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+	sync::RwLock,
+};
+
+use lazy_regex::regex_is_match;
+use serde::Deserialize;
+use tokio::{fs, sync::Mutex};
+
+use crate::{cached_hasher::FileInfoCache, packwiz_cache::PackwizCache};
+
+/// Everything needed to serve a single modpack, all paths are canonicalized
+#[derive(Debug)]
+pub struct Pack {
+	/// Path to drakermore config file
+	pub config: PathBuf,
+	/// The folders in this folder will be copied to the user's .minecraft folder verbatim
+	pub copy_dir: PathBuf,
+	/// Path to where the mods where downloaded by the scraper
+	pub download_dir: PathBuf,
+	/// The prefix to use for URLs pointing to this pack including its access secret, without a trailing slash
+	pub url_prefix: String,
+	/// The path segment every one of the pack's URLs starts with, the pack is public without one
+	pub access_secret: Option<String>,
+	/// The Minecraft server's mods folder, which gets rebuilt from the server and both jars at startup
+	pub server_mods_dir: Option<PathBuf>,
+	/// The bearer token the admin endpoints need, they're disabled without one
+	pub admin_token: Option<String>,
+	pub file_info: FileInfoCache,
+	pub packwiz: RwLock<PackwizCache>,
+	/// Held while the packwiz cache is being updated, since updates copy the cache and write the copy back
+	pub packwiz_update: Mutex<()>,
+}
+impl Pack {
+	pub fn new(config: PathBuf, copy_dir: PathBuf, download_dir: PathBuf, url_prefix: String) -> anyhow::Result<Self> {
+		Ok(Self {
+			config: config.canonicalize()?,
+			copy_dir: copy_dir.canonicalize()?,
+			download_dir: download_dir.canonicalize()?,
+			url_prefix,
+			access_secret: None,
+			server_mods_dir: None,
+			admin_token: None,
+			file_info: FileInfoCache::default(),
+			packwiz: RwLock::default(),
+			packwiz_update: Mutex::default(),
+		})
+	}
+	/// Puts all of the pack's URLs behind a secret path segment. Since it's part of every URL we generate, packwiz
+	/// and the launchers don't need to know about it.
+	pub fn set_access_secret(&mut self, access_secret: String) -> anyhow::Result<()> {
+		if !regex_is_match!(r"^[a-zA-Z0-9_\-]+$", &access_secret) {
+			anyhow::bail!("access secrets should only contain letters, numbers, \"-\" or \"_\"");
+		}
+		self.url_prefix = format!("{}/{access_secret}", self.url_prefix);
+		self.access_secret = Some(access_secret);
+		Ok(())
+	}
+}
+
+/// Top-level config used when serving multiple packs from one process
+#[derive(Debug, Deserialize, Clone)]
+pub struct DrakermoreServerConfig {
+	/// Packs keyed by the name used in their URL, e.g. `/packs/{name}/packwiz/pack.toml`
+	pub packs: BTreeMap<String, DrakermoreServerPackPaths>,
+}
+#[derive(Debug, Deserialize, Clone)]
+pub struct DrakermoreServerPackPaths {
+	pub config: PathBuf,
+	pub copy_dir: PathBuf,
+	pub download_dir: PathBuf,
+	#[serde(default)]
+	pub server_mods_dir: Option<PathBuf>,
+	/// Overrides the server-wide access secret for this pack
+	#[serde(default)]
+	pub access_secret: Option<String>,
+}
+impl DrakermoreServerConfig {
+	/// Reads the server config, relative paths are resolved relative to the server config's folder
+	pub async fn read_from_file(file_path: &Path) -> anyhow::Result<Self> {
+		let mut config: Self = toml::from_str(&fs::read_to_string(file_path).await?)?;
+		let base_dir = file_path.parent().unwrap_or(Path::new("."));
+		if config.packs.is_empty() {
+			anyhow::bail!("{} doesn't list any packs", file_path.display());
+		}
+		for (pack_name, pack_paths) in config.packs.iter_mut() {
+			if !regex_is_match!(r"^[a-zA-Z0-9_\-]+$", pack_name) {
+				anyhow::bail!("pack name \"{pack_name}\" should only contain letters, numbers, \"-\" or \"_\"");
+			}
+			for path in [
+				&mut pack_paths.config,
+				&mut pack_paths.copy_dir,
+				&mut pack_paths.download_dir,
+			] {
+				*path = base_dir.join(&path);
+			}
+			if let Some(server_mods_dir) = pack_paths.server_mods_dir.as_mut() {
+				*server_mods_dir = base_dir.join(&server_mods_dir);
+			}
+		}
+		Ok(config)
+	}
+	/// Creates a `Pack` for each configured pack, with URLs nested under `{url_prefix}/packs/{name}`, and
+	/// `/{access_secret}` after that if the pack has one
+	pub fn into_packs<'a>(
+		self,
+		url_prefix: &'a str,
+		access_secret: Option<&'a str>,
+	) -> impl Iterator<Item = anyhow::Result<(String, Pack)>> + 'a {
+		self.packs.into_iter().map(move |(pack_name, pack_paths)| {
+			let mut pack = Pack::new(
+				pack_paths.config,
+				pack_paths.copy_dir,
+				pack_paths.download_dir,
+				format!("{url_prefix}/packs/{pack_name}"),
+			)?;
+			pack.server_mods_dir = pack_paths.server_mods_dir;
+			if let Some(access_secret) = pack_paths.access_secret.or(access_secret.map(String::from)) {
+				pack.set_access_secret(access_secret)?;
+			}
+			Ok((pack_name, pack))
+		})
+	}
+}
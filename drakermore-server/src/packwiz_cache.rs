@@ -0,0 +1,730 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, BTreeSet, HashSet},
+	io::ErrorKind as IoErrorKind,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::{Duration, SystemTime},
+};
+
+use futures::StreamExt;
+use notify::{
+	event::{AccessKind, AccessMode},
+	EventKind, RecursiveMode, Watcher,
+};
+use sha2::{Digest, Sha512};
+use tokio::{fs, sync::mpsc};
+
+use crate::{
+	jar_check::{cached_jars, duplicate_problems, version_incompatibilities},
+	nested_dirs::subfiles_in_folder,
+	pack::Pack,
+	safe_path::{resolve_safe_path, SafePathError},
+	schemas::{
+		DrakermoreModConfig, ModSource, PackwizFormatVersion, PackwizHashFormat, PackwizIndex, PackwizIndexFile,
+		PackwizMetadata, PackwizMetadataIndex, PackwizMetadataVersions, PackwizMod, PackwizModDownload,
+		PackwizModOption, PackwizModSide,
+	},
+};
+
+/// How long to wait for more filesystem events before rebuilding, so copying a bunch of files only causes one rebuild
+const WATCHER_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Files next to a jar which affect its packwiz metadata
+pub const JAR_SIDECAR_SUFFIXES: [&str; 3] = [".name.txt", ".option.toml", ".source.toml"];
+
+/// A generated packwiz file, along with its hex-encoded sha512 hash
+#[derive(Debug, Clone)]
+pub struct CachedPackwizFile {
+	pub contents: Arc<str>,
+	pub sha512: Arc<str>,
+	/// When the contents last actually changed
+	pub last_modified: SystemTime,
+}
+impl CachedPackwizFile {
+	/// Regenerating a file doesn't mean it changed, so keep the old timestamp if the contents are the same
+	fn keep_last_modified(&mut self, previous: Option<&CachedPackwizFile>) {
+		if let Some(previous) = previous.filter(|previous| previous.sha512 == self.sha512) {
+			self.last_modified = previous.last_modified;
+		}
+	}
+}
+impl Default for CachedPackwizFile {
+	fn default() -> Self {
+		Self {
+			contents: Default::default(),
+			sha512: Default::default(),
+			last_modified: SystemTime::UNIX_EPOCH,
+		}
+	}
+}
+impl From<String> for CachedPackwizFile {
+	fn from(contents: String) -> Self {
+		Self {
+			sha512: hex::encode(Sha512::digest(&contents)).into(),
+			contents: contents.into(),
+			last_modified: SystemTime::now(),
+		}
+	}
+}
+
+/// A jar's packwiz metadata, along with what the other pack formats need to know about it
+#[derive(Debug, Clone)]
+pub struct CachedModFile {
+	/// The name players see, from the `.name.txt` file or the jar's fabric.mod.json
+	pub name: String,
+	/// The download dir folder the jar is in
+	pub realm: PackwizModSide,
+	/// Where the mod should be installed, which can be narrower than the realm
+	pub side: PackwizModSide,
+	/// Whether players can choose not to install the mod
+	pub optional: bool,
+	/// Where the jar was downloaded from, if we know
+	pub source: Option<ModSource>,
+	pub metadata: CachedPackwizFile,
+}
+
+/// A copy dir file's packwiz metadata, along with where the file actually is
+#[derive(Debug, Clone)]
+pub struct CachedCopyFile {
+	/// Path relative to the copy dir
+	pub source: PathBuf,
+	pub side: PackwizModSide,
+	pub metadata: CachedPackwizFile,
+}
+
+/// Every packwiz file we serve, kept in memory so requests don't have to touch the disk
+#[derive(Debug, Default, Clone)]
+pub struct PackwizCache {
+	/// Keyed by jar file name
+	pub mods: BTreeMap<String, CachedModFile>,
+	/// Keyed by the path the file ends up at in the instance, which is the path relative to the copy dir without any
+	/// side folder
+	pub copy_files: BTreeMap<PathBuf, CachedCopyFile>,
+	/// Jar file names which are in more than one realm, mapped to the realms which aren't being served
+	pub shadowed_jars: BTreeMap<String, Vec<PackwizModSide>>,
+	pub index: CachedPackwizFile,
+	pub pack: CachedPackwizFile,
+}
+impl PackwizCache {
+	fn index_string(&self) -> anyhow::Result<String> {
+		let mut files = Vec::with_capacity(self.mods.len() + self.copy_files.len());
+		for (jar_file_name, cached_mod) in self.mods.iter() {
+			let jar_file_name = &jar_file_name[0..(jar_file_name.len() - 4)];
+			files.push(PackwizIndexFile {
+				file: format!("mods/{jar_file_name}.pw.toml").into(),
+				hash: Cow::Borrowed(&cached_mod.metadata.sha512),
+				metafile: true,
+			});
+		}
+		for (relative_file_path, cached_file) in self.copy_files.iter() {
+			files.push(PackwizIndexFile {
+				file: format!("{}.pw.toml", relative_file_path.to_string_lossy()).into(),
+				hash: Cow::Borrowed(&cached_file.metadata.sha512),
+				metafile: true,
+			});
+		}
+		Ok(toml::to_string_pretty(&PackwizIndex {
+			hash_format: PackwizHashFormat::Sha512,
+			files,
+		})?)
+	}
+}
+
+/// Returns None if the jar is being left out of the pack for needing a different Minecraft or loader version
+async fn pw_mod_metadata(
+	pack: &Pack,
+	realm: PackwizModSide,
+	jar_file_name: PathBuf,
+	modpack: &DrakermoreModConfig,
+) -> anyhow::Result<Option<CachedModFile>> {
+	let jar_file_name_str = jar_file_name.to_string_lossy();
+	if !jar_file_name_str.ends_with(".jar") {
+		anyhow::bail!("attempted to show mod metadata for {realm}/{jar_file_name_str} which doesn't end in \".jar\"");
+	}
+	let mut jar_full_path = pack.download_dir.clone();
+	jar_full_path.push(realm.to_string());
+	resolve_safe_path(&jar_full_path, &jar_file_name_str).await?;
+	jar_full_path.push(&jar_file_name);
+	let jar_info = pack.file_info.get_info_from_file(&jar_full_path).await?;
+	if let Some(fabric_mod) = &jar_info.fabric_mod {
+		let incompatibilities = version_incompatibilities(modpack, fabric_mod);
+		for incompatibility in incompatibilities.iter() {
+			tracing::warn!("{realm}/{jar_file_name_str} is incompatible: {incompatibility}");
+		}
+		if modpack.exclude_incompatible_jars && !incompatibilities.is_empty() {
+			tracing::warn!("Leaving {realm}/{jar_file_name_str} out of the pack since it's incompatible");
+			return Ok(None);
+		}
+	}
+
+	// A hand-written .name.txt file still takes priority over what the mod says its name is
+	jar_full_path.pop();
+	jar_full_path.push(format!("{jar_file_name_str}.name.txt"));
+	let mod_name = fs::read_to_string(&jar_full_path)
+		.await
+		.map(|mut string| {
+			string.truncate(string.trim_end().len());
+			string
+		})
+		.unwrap_or_else(|_| match &jar_info.fabric_mod {
+			Some(fabric_mod) => fabric_mod.display_name().into(),
+			None => jar_file_name_str[0..(jar_file_name_str.len() - 4)].into(),
+		});
+
+	// Same goes for the .option.toml file and the config's mod options
+	jar_full_path.pop();
+	jar_full_path.push(format!("{jar_file_name_str}.option.toml"));
+	let mod_option = match fs::read_to_string(&jar_full_path).await {
+		Ok(option_toml) => Some(toml::from_str::<PackwizModOption>(&option_toml)?),
+		Err(err) if err.kind() == IoErrorKind::NotFound => None,
+		Err(err) => return Err(err.into()),
+	};
+	jar_full_path.pop();
+	jar_full_path.push(format!("{jar_file_name_str}.source.toml"));
+	let mod_source = match fs::read_to_string(&jar_full_path).await {
+		Ok(source_toml) => Some(toml::from_str::<ModSource>(&source_toml)?),
+		Err(err) if err.kind() == IoErrorKind::NotFound => None,
+		Err(err) => return Err(err.into()),
+	};
+	let mod_options = &modpack.mod_options;
+	let mod_option = mod_option.as_ref().or_else(|| {
+		mod_options.get(jar_file_name_str.as_ref()).or_else(|| {
+			jar_info
+				.fabric_mod
+				.as_ref()
+				.and_then(|fabric_mod| mod_options.get(&fabric_mod.id))
+		})
+	});
+
+	if let Some(fabric_mod) = jar_info
+		.fabric_mod
+		.as_ref()
+		.filter(|fabric_mod| fabric_mod.environment.contradicts(realm))
+	{
+		tracing::warn!(
+			"{realm}/{jar_file_name_str} is in the wrong folder, {} doesn't run on the {realm} side",
+			fabric_mod.id
+		);
+	}
+	let side = jar_info
+		.fabric_mod
+		.as_ref()
+		.map(|fabric_mod| fabric_mod.environment.narrow_side(realm))
+		.unwrap_or(realm);
+
+	let metadata = toml::to_string_pretty(&PackwizMod {
+		download: PackwizModDownload {
+			url: format!("{}/jars/{realm}/{jar_file_name_str}", &pack.url_prefix).into(),
+			hash_format: PackwizHashFormat::Sha512,
+			hash: Cow::Borrowed(&jar_info.sha512),
+		},
+		name: &mod_name,
+		filename: jar_file_name_str,
+		side,
+		option: mod_option,
+		update: mod_source.as_ref().map(ModSource::packwiz_update).transpose()?,
+	})?;
+	Ok(Some(CachedModFile {
+		name: mod_name,
+		realm,
+		side,
+		optional: mod_option.is_some_and(|mod_option| mod_option.optional),
+		source: mod_source,
+		metadata: metadata.into(),
+	}))
+}
+async fn pw_copy_metadata_string(pack: &Pack, file_path: &Path, side: PackwizModSide) -> anyhow::Result<String> {
+	let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+	let file_path_str = file_path.to_string_lossy();
+	resolve_safe_path(&pack.copy_dir, &file_path_str).await?;
+	let full_file_path = pack.copy_dir.join(file_path);
+
+	Ok(toml::to_string_pretty(&PackwizMod {
+		download: PackwizModDownload {
+			url: format!("{}/copy_files/{file_path_str}", &pack.url_prefix).into(),
+			hash_format: PackwizHashFormat::Sha512,
+			hash: Cow::Borrowed(&pack.file_info.get_hash_from_file(&full_file_path).await?),
+		},
+		name: &file_name,
+		filename: file_name.clone(),
+		side,
+		option: None,
+		update: None,
+	})?)
+}
+async fn pw_pack_string(pack: &Pack, index: &CachedPackwizFile) -> anyhow::Result<String> {
+	let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+
+	Ok(toml::to_string_pretty(&PackwizMetadata {
+		name: modpack.name.into(),
+		author: modpack.pack_author.into(),
+		version: modpack.pack_version.into(),
+		pack_format: PackwizFormatVersion::V1_1_0,
+		versions: PackwizMetadataVersions::new(
+			modpack.minecraft_version.into(),
+			modpack.loader,
+			modpack.loader_version.into(),
+		),
+		index: PackwizMetadataIndex {
+			file: "index.toml".into(),
+			hash_format: PackwizHashFormat::Sha512,
+			hash: Cow::Borrowed(&index.sha512),
+		},
+	})?)
+}
+
+/// Returns every realm the specified jar can be found in, the first one is the one which gets served
+pub async fn find_jar_realms(pack: &Pack, jar_file_name: &Path) -> anyhow::Result<Vec<PackwizModSide>> {
+	let mut realms = Vec::new();
+	let mut jar_full_path = pack.download_dir.clone();
+	for realm in PackwizModSide::all() {
+		jar_full_path.push(realm.to_string());
+		jar_full_path.push(jar_file_name);
+		if fs::try_exists(&jar_full_path).await? {
+			realms.push(realm);
+		}
+		jar_full_path.pop();
+		jar_full_path.pop();
+	}
+	Ok(realms)
+}
+
+/// Files in a top-level `client/`, `server/` or `both/` folder get that side and are installed without the folder,
+/// otherwise the most specific rule in `copy_file_sides` applies, falling back to the client. Returns the side and the
+/// path the file ends up at in the instance.
+fn copy_file_side<'a>(
+	file_path: &'a Path,
+	copy_file_sides: &BTreeMap<PathBuf, PackwizModSide>,
+) -> (PackwizModSide, &'a Path) {
+	let mut components = file_path.components();
+	let side_folder = components
+		.next()
+		.and_then(|component| component.as_os_str().to_str())
+		.and_then(|component| component.parse::<PackwizModSide>().ok());
+	if let Some(side) = side_folder.filter(|_| !components.as_path().as_os_str().is_empty()) {
+		return (side, components.as_path());
+	}
+	let side = file_path
+		.ancestors()
+		.find_map(|ancestor| copy_file_sides.get(ancestor))
+		.copied()
+		.unwrap_or(PackwizModSide::Client);
+	(side, file_path)
+}
+
+/// Files which point outside of the pack's folders (e.g. through symlinks) are left out of the pack
+fn skip_forbidden<T>(metadata: anyhow::Result<T>) -> anyhow::Result<Option<T>> {
+	match metadata.map_err(|err| err.downcast::<SafePathError>()) {
+		Ok(metadata) => Ok(Some(metadata)),
+		Err(Ok(SafePathError::Forbidden(path))) => {
+			tracing::warn!("Leaving {path} out of the pack since it points outside of the pack's folders");
+			Ok(None)
+		},
+		Err(Ok(err)) => Err(err.into()),
+		Err(Err(err)) => Err(err),
+	}
+}
+
+async fn cache_mod(
+	pack: &Pack,
+	cache: &mut PackwizCache,
+	jar_file_name: String,
+	modpack: &DrakermoreModConfig,
+) -> anyhow::Result<()> {
+	let mut realms = find_jar_realms(pack, Path::new(&jar_file_name)).await?.into_iter();
+	let metadata = match realms.next() {
+		Some(realm) => {
+			skip_forbidden(pw_mod_metadata(pack, realm, jar_file_name.clone().into(), modpack).await)?.flatten()
+		},
+		None => None,
+	};
+	let shadowed_realms: Vec<_> = realms.collect();
+	if shadowed_realms.is_empty() {
+		cache.shadowed_jars.remove(&jar_file_name);
+	} else {
+		cache.shadowed_jars.insert(jar_file_name.clone(), shadowed_realms);
+	}
+	match metadata {
+		Some(metadata) => {
+			cache.mods.insert(jar_file_name, metadata);
+		},
+		None => {
+			cache.mods.remove(&jar_file_name);
+			for realm in PackwizModSide::all() {
+				pack.file_info
+					.forget(&pack.download_dir.join(realm.to_string()).join(&jar_file_name));
+			}
+		},
+	}
+	Ok(())
+}
+
+async fn cache_copy_file(
+	pack: &Pack,
+	cache: &mut PackwizCache,
+	file_path: PathBuf,
+	copy_file_sides: &BTreeMap<PathBuf, PackwizModSide>,
+) -> anyhow::Result<()> {
+	let (side, install_path) = copy_file_side(&file_path, copy_file_sides);
+	let install_path = install_path.to_path_buf();
+	if let Some(existing) = cache.copy_files.get(&install_path) {
+		tracing::warn!(
+			"Leaving {} out of the pack since {} is already installed to {}",
+			file_path.display(),
+			existing.source.display(),
+			install_path.display()
+		);
+		return Ok(());
+	}
+	if let Some(metadata) = skip_forbidden(pw_copy_metadata_string(pack, &file_path, side).await)? {
+		cache.copy_files.insert(
+			install_path,
+			CachedCopyFile {
+				source: file_path,
+				side,
+				metadata: metadata.into(),
+			},
+		);
+	}
+	Ok(())
+}
+
+async fn cache_copy_files(
+	pack: &Pack,
+	cache: &mut PackwizCache,
+	relative_path: &Path,
+	copy_file_sides: &BTreeMap<PathBuf, PackwizModSide>,
+) -> anyhow::Result<()> {
+	// The path could've been a folder that was removed, so everything under it has to go
+	cache.copy_files.retain(|_, cached_file| {
+		if cached_file.source.starts_with(relative_path) {
+			pack.file_info.forget(&pack.copy_dir.join(&cached_file.source));
+			false
+		} else {
+			true
+		}
+	});
+	let full_path = pack.copy_dir.join(relative_path);
+	let Ok(metadata) = fs::metadata(&full_path).await else {
+		return Ok(());
+	};
+	if metadata.is_file() {
+		return cache_copy_file(pack, cache, relative_path.into(), copy_file_sides).await;
+	}
+	let mut copy_files = subfiles_in_folder(full_path, true);
+	while let Some(full_file_path) = copy_files.next().await {
+		let file_path = full_file_path?.strip_prefix(&pack.copy_dir)?.to_path_buf();
+		cache_copy_file(pack, cache, file_path, copy_file_sides).await?;
+	}
+	Ok(())
+}
+
+async fn finish_cache(
+	pack: &Pack,
+	mut cache: PackwizCache,
+	modpack: &DrakermoreModConfig,
+	rebuild_pack: bool,
+) -> anyhow::Result<()> {
+	let duplicates = duplicate_problems(&cached_jars(pack, &cache).await?);
+	for duplicate in duplicates.iter() {
+		tracing::warn!("{duplicate}");
+	}
+	if modpack.reject_duplicate_jars && !duplicates.is_empty() {
+		anyhow::bail!(
+			"not publishing the packwiz index for {} since it has {} duplicate mods",
+			pack.config.display(),
+			duplicates.len()
+		);
+	}
+	let mut index: CachedPackwizFile = cache.index_string()?.into();
+	{
+		let previous = pack.packwiz.read().unwrap();
+		for (jar_file_name, cached_file) in cache.mods.iter_mut() {
+			cached_file
+				.metadata
+				.keep_last_modified(previous.mods.get(jar_file_name).map(|previous| &previous.metadata));
+		}
+		for (install_path, cached_file) in cache.copy_files.iter_mut() {
+			cached_file
+				.metadata
+				.keep_last_modified(previous.copy_files.get(install_path).map(|previous| &previous.metadata));
+		}
+		index.keep_last_modified(Some(&previous.index));
+	}
+	if rebuild_pack || index.sha512 != cache.index.sha512 {
+		let mut pack_file: CachedPackwizFile = pw_pack_string(pack, &index).await?.into();
+		pack_file.keep_last_modified(Some(&pack.packwiz.read().unwrap().pack));
+		cache.pack = pack_file;
+	}
+	cache.index = index;
+	*pack.packwiz.write().unwrap() = cache;
+	Ok(())
+}
+
+/// Generates every packwiz file from scratch
+pub async fn rebuild_packwiz_cache(pack: &Pack) -> anyhow::Result<()> {
+	let _update_guard = pack.packwiz_update.lock().await;
+	rebuild_cache(pack).await
+}
+async fn rebuild_cache(pack: &Pack) -> anyhow::Result<()> {
+	let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+	let mut cache = PackwizCache::default();
+	let mut seen_jars = HashSet::new();
+	for realm in PackwizModSide::all() {
+		let mut dir_reader = fs::read_dir(pack.download_dir.join(realm.to_string())).await?;
+		while let Some(dir_entry) = dir_reader.next_entry().await? {
+			let jar_file_name = dir_entry.file_name().to_string_lossy().into_owned();
+			if !jar_file_name.ends_with(".jar") || !dir_entry.file_type().await?.is_file() {
+				continue;
+			}
+			if !seen_jars.insert(jar_file_name.clone()) {
+				cache.shadowed_jars.entry(jar_file_name).or_default().push(realm);
+				continue;
+			}
+			if let Some(metadata) =
+				skip_forbidden(pw_mod_metadata(pack, realm, jar_file_name.clone().into(), &modpack).await)?.flatten()
+			{
+				cache.mods.insert(jar_file_name, metadata);
+			}
+		}
+	}
+	cache_copy_files(pack, &mut cache, Path::new(""), &modpack.copy_file_sides).await?;
+	finish_cache(pack, cache, &modpack, true).await
+}
+
+/// Only regenerates the packwiz files affected by the specified paths changing
+pub async fn update_packwiz_cache(pack: &Pack, changed_paths: HashSet<PathBuf>) -> anyhow::Result<()> {
+	let _update_guard = pack.packwiz_update.lock().await;
+	let mut changed_jars = BTreeSet::new();
+	let mut changed_copy_paths = BTreeSet::new();
+	for changed_path in changed_paths {
+		if changed_path == pack.config {
+			// The config decides copy dir file sides and mod options, so everything could've changed
+			return rebuild_cache(pack).await;
+		} else if let Ok(relative_path) = changed_path.strip_prefix(&pack.download_dir) {
+			let mut components = relative_path.iter().map(|component| component.to_string_lossy());
+			let (Some(realm), file_name) = (components.next(), components.next()) else {
+				continue;
+			};
+			if realm.parse::<PackwizModSide>().is_err() {
+				continue;
+			}
+			let Some(file_name) = file_name else {
+				// A whole realm folder got moved around
+				return rebuild_cache(pack).await;
+			};
+			let jar_file_name = JAR_SIDECAR_SUFFIXES
+				.iter()
+				.find_map(|suffix| file_name.strip_suffix(suffix))
+				.unwrap_or(&file_name);
+			if jar_file_name.ends_with(".jar") {
+				changed_jars.insert(jar_file_name.to_string());
+			}
+		} else if let Ok(relative_path) = changed_path.strip_prefix(&pack.copy_dir) {
+			changed_copy_paths.insert(relative_path.to_path_buf());
+		}
+	}
+	if changed_jars.is_empty() && changed_copy_paths.is_empty() {
+		return Ok(());
+	}
+
+	let modpack = DrakermoreModConfig::read_from_file(&pack.config).await?;
+	let mut cache = pack.packwiz.read().unwrap().clone();
+	for jar_file_name in changed_jars {
+		tracing::info!("Updating packwiz metadata for {jar_file_name}");
+		cache_mod(pack, &mut cache, jar_file_name, &modpack).await?;
+	}
+	for relative_path in changed_copy_paths {
+		tracing::info!("Updating packwiz metadata for {}", relative_path.display());
+		cache_copy_files(pack, &mut cache, &relative_path, &modpack.copy_file_sides).await?;
+	}
+	finish_cache(pack, cache, &modpack, false).await
+}
+
+/// Watches the pack's download dir, copy dir and config file, keeping the packwiz cache up-to-date
+pub fn watch_pack(pack: Arc<Pack>) -> anyhow::Result<()> {
+	let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+	let mut watcher = notify::recommended_watcher(move |event| {
+		// The receiver only goes away when the process is shutting down
+		let _ = event_sender.send(event);
+	})?;
+	watcher.watch(&pack.download_dir, RecursiveMode::Recursive)?;
+	watcher.watch(&pack.copy_dir, RecursiveMode::Recursive)?;
+	// Editors usually replace files instead of writing to them, so we watch the folder instead of the file itself
+	watcher.watch(
+		pack.config.parent().unwrap_or(Path::new("/")),
+		RecursiveMode::NonRecursive,
+	)?;
+	tokio::spawn(async move {
+		// The watcher stops when it's dropped, so it lives as long as this task
+		let _watcher = watcher;
+		while let Some(event) = event_receiver.recv().await {
+			let mut changed_paths = HashSet::new();
+			let mut add_event = |event: notify::Result<notify::Event>| match event {
+				// We read these files ourselves while rebuilding, so we only care about files which were written to
+				Ok(event) if matches!(event.kind, EventKind::Access(kind) if kind != AccessKind::Close(AccessMode::Write)) =>
+					{},
+				Ok(event) => changed_paths.extend(event.paths),
+				Err(err) => tracing::error!("File watcher error: {err}"),
+			};
+			add_event(event);
+			tokio::time::sleep(WATCHER_DEBOUNCE).await;
+			while let Ok(event) = event_receiver.try_recv() {
+				add_event(event);
+			}
+			if let Err(err) = update_packwiz_cache(&pack, changed_paths).await {
+				tracing::error!(
+					"Failed to update the packwiz files for {}: {err:?}",
+					pack.config.display()
+				);
+			}
+		}
+	});
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::test_pack;
+
+	/// Builds the packwiz cache for a pack with the specified files, and returns the jars' pw.toml files
+	async fn pw_tomls(config_overrides: &str, files: &[(&str, &str)]) -> BTreeMap<String, toml::Value> {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let pack = test_pack(temp_dir.path(), config_overrides);
+		for (path, contents) in files {
+			std::fs::write(temp_dir.path().join(path), contents).unwrap();
+		}
+		rebuild_packwiz_cache(&pack).await.unwrap();
+		let cache = pack.packwiz.read().unwrap().clone();
+		cache
+			.mods
+			.into_iter()
+			.map(|(jar_file_name, cached_mod)| (jar_file_name, toml::from_str(&cached_mod.metadata.contents).unwrap()))
+			.collect()
+	}
+
+	#[test]
+	fn copy_file_sides_come_from_side_folders_then_rules() {
+		let copy_file_sides = BTreeMap::from([
+			(PathBuf::from("config"), PackwizModSide::Both),
+			(PathBuf::from("config/server.json"), PackwizModSide::Server),
+		]);
+		for (file_path, expected_side, expected_install_path) in [
+			("server/config/foo.json", PackwizModSide::Server, "config/foo.json"),
+			("both/options.txt", PackwizModSide::Both, "options.txt"),
+			("config/foo.json", PackwizModSide::Both, "config/foo.json"),
+			("config/server.json", PackwizModSide::Server, "config/server.json"),
+			("options.txt", PackwizModSide::Client, "options.txt"),
+			("server", PackwizModSide::Client, "server"),
+		] {
+			assert_eq!(
+				copy_file_side(Path::new(file_path), &copy_file_sides),
+				(expected_side, Path::new(expected_install_path)),
+				"{file_path}"
+			);
+		}
+	}
+	#[tokio::test]
+	async fn mod_options_become_option_tables() {
+		let pw_tomls = pw_tomls(
+			r#"
+[mod_options."iris.jar"]
+optional = true
+description = "Shaders"
+
+[mod_options."sodium.jar"]
+optional = true
+"#,
+			&[
+				("download/client/iris.jar", "iris jar"),
+				("download/client/sodium.jar", "sodium jar"),
+				(
+					"download/client/sodium.jar.option.toml",
+					"optional = true\ndefault = true\ndescription = \"Faster rendering\"\n",
+				),
+				("download/both/lithium.jar", "lithium jar"),
+			],
+		)
+		.await;
+		assert_eq!(
+			pw_tomls["iris.jar"]["option"],
+			toml::toml! {
+				optional = true
+				default = false
+				description = "Shaders"
+			}
+			.into()
+		);
+		// The .option.toml file takes priority over the config
+		assert_eq!(
+			pw_tomls["sodium.jar"]["option"],
+			toml::toml! {
+				optional = true
+				default = true
+				description = "Faster rendering"
+			}
+			.into()
+		);
+		assert!(pw_tomls["lithium.jar"].get("option").is_none());
+	}
+
+	#[tokio::test]
+	async fn mod_sources_become_update_tables() {
+		let pw_tomls = pw_tomls(
+			"",
+			&[
+				("download/both/sodium.jar", "modrinth jar"),
+				(
+					"download/both/sodium.jar.source.toml",
+					"repo = \"modrinth\"\nproject_id = \"AANobbMI\"\nversion_id = \"OihdIimA\"\n",
+				),
+				("download/both/jei.jar", "curseforge jar"),
+				(
+					"download/both/jei.jar.source.toml",
+					"repo = \"curseforge\"\nproject_id = \"238222\"\nversion_id = \"5846810\"\n",
+				),
+				("download/both/homemade.jar", "jar from nowhere"),
+			],
+		)
+		.await;
+		assert_eq!(
+			pw_tomls["sodium.jar"]["update"],
+			toml::toml! { [modrinth]
+				mod-id = "AANobbMI"
+				version = "OihdIimA"
+			}
+			.into()
+		);
+		assert_eq!(
+			pw_tomls["jei.jar"]["update"],
+			toml::toml! { [curseforge]
+				project-id = 238222
+				file-id = 5846810
+			}
+			.into()
+		);
+		assert!(pw_tomls["homemade.jar"].get("update").is_none());
+	}
+	#[tokio::test]
+	async fn concurrent_updates_all_make_it_into_the_cache() {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let pack = test_pack(temp_dir.path(), "");
+		rebuild_packwiz_cache(&pack).await.unwrap();
+		let jar_paths: Vec<_> = (0..8)
+			.map(|i| pack.download_dir.join(format!("both/mod-{i}.jar")))
+			.collect();
+		for jar_path in jar_paths.iter() {
+			std::fs::write(jar_path, "not really a jar").unwrap();
+		}
+		futures::future::try_join_all(
+			jar_paths
+				.iter()
+				.map(|jar_path| update_packwiz_cache(&pack, HashSet::from([jar_path.clone()]))),
+		)
+		.await
+		.unwrap();
+		assert_eq!(pack.packwiz.read().unwrap().mods.len(), jar_paths.len());
+	}
+}
@@ -0,0 +1,407 @@
+use serde::Deserialize;
+use serde_repr::Deserialize_repr;
+
+use super::{http_client, ResolvedMod};
+use crate::schemas::{ModListItem, ModLoader, ModRepo, ModRepoChannel, ModSource, PackwizModSide};
+
+pub const CURSEFORGE_API_URL: &str = "https://api.curseforge.com";
+/// CurseForge's id for Minecraft
+const MINECRAFT_GAME_ID: u32 = 432;
+/// CurseForge's id for the "Mods" category, as opposed to modpacks, resource packs, etc.
+const MODS_CLASS_ID: u32 = 6;
+/// The most files CurseForge will return in one page
+const FILES_PAGE_SIZE: u32 = 50;
+
+/// Finds mod files through the CurseForge Core API, see https://docs.curseforge.com/rest-api/
+#[derive(Debug, Clone)]
+pub struct CurseforgeResolver {
+	http: reqwest::Client,
+	/// Without a trailing slash, e.g. `CURSEFORGE_API_URL`
+	base_url: String,
+	api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurseforgeResponse<T> {
+	data: T,
+	pagination: Option<CurseforgePagination>,
+}
+#[derive(Debug, Deserialize)]
+struct CurseforgePagination {
+	index: u32,
+	#[serde(rename = "resultCount")]
+	result_count: u32,
+	#[serde(rename = "totalCount")]
+	total_count: u32,
+}
+#[derive(Debug, Deserialize)]
+struct CurseforgeMod {
+	id: u32,
+	name: String,
+	slug: String,
+	/// Authors can opt out of their files being downloaded by anything other than the CurseForge app
+	#[serde(rename = "allowModDistribution")]
+	allow_mod_distribution: Option<bool>,
+}
+#[derive(Debug, Deserialize)]
+struct CurseforgeFile {
+	id: u32,
+	#[serde(rename = "displayName")]
+	display_name: String,
+	#[serde(rename = "fileName")]
+	file_name: String,
+	#[serde(rename = "releaseType")]
+	release_type: CurseforgeReleaseType,
+	/// ISO 8601, so comparing these as strings also compares them chronologically
+	#[serde(rename = "fileDate")]
+	file_date: String,
+	/// Null when the author doesn't allow third party downloads
+	#[serde(rename = "downloadUrl")]
+	download_url: Option<String>,
+	#[serde(rename = "fileLength")]
+	file_length: u64,
+	#[serde(rename = "fileFingerprint")]
+	file_fingerprint: u32,
+	hashes: Vec<CurseforgeFileHash>,
+	/// Besides Minecraft versions this also has loaders and, for newer files, "Client" and/or "Server"
+	#[serde(rename = "gameVersions")]
+	game_versions: Vec<String>,
+	#[serde(rename = "isAvailable", default = "default_true")]
+	is_available: bool,
+	#[serde(default)]
+	dependencies: Vec<CurseforgeFileDependency>,
+}
+#[derive(Debug, Deserialize)]
+struct CurseforgeFileDependency {
+	#[serde(rename = "modId")]
+	mod_id: u32,
+	#[serde(rename = "relationType")]
+	relation_type: CurseforgeRelationType,
+}
+#[derive(Debug, Deserialize_repr, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum CurseforgeRelationType {
+	EmbeddedLibrary = 1,
+	OptionalDependency = 2,
+	RequiredDependency = 3,
+	Tool = 4,
+	Incompatible = 5,
+	Include = 6,
+}
+fn default_true() -> bool {
+	true
+}
+impl CurseforgeFile {
+	/// Files which don't say what they support get installed everywhere, just to be safe
+	fn side(&self) -> PackwizModSide {
+		let has_tag = |tag: &str| self.game_versions.iter().any(|game_version| game_version == tag);
+		match (has_tag("Client"), has_tag("Server")) {
+			(true, false) => PackwizModSide::Client,
+			(false, true) => PackwizModSide::Server,
+			_ => PackwizModSide::Both,
+		}
+	}
+	fn sha1(&self) -> Option<&str> {
+		self.hashes
+			.iter()
+			.find(|hash| hash.algo == CurseforgeHashAlgo::Sha1)
+			.map(|hash| hash.value.as_str())
+	}
+}
+#[derive(Debug, Deserialize)]
+struct CurseforgeFileHash {
+	value: String,
+	algo: CurseforgeHashAlgo,
+}
+#[derive(Debug, Deserialize_repr, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum CurseforgeHashAlgo {
+	Sha1 = 1,
+	Md5 = 2,
+}
+#[derive(Debug, Deserialize_repr, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum CurseforgeReleaseType {
+	Release = 1,
+	Beta = 2,
+	Alpha = 3,
+}
+impl From<CurseforgeReleaseType> for ModRepoChannel {
+	fn from(release_type: CurseforgeReleaseType) -> Self {
+		match release_type {
+			CurseforgeReleaseType::Release => ModRepoChannel::Release,
+			CurseforgeReleaseType::Beta => ModRepoChannel::Beta,
+			CurseforgeReleaseType::Alpha => ModRepoChannel::Alpha,
+		}
+	}
+}
+/// CurseForge's `modLoaderType` ids
+fn curseforge_loader_types(loader: ModLoader) -> &'static [u8] {
+	match loader {
+		ModLoader::Forge => &[1],
+		ModLoader::Fabric => &[4],
+		// Quilt can load Fabric mods
+		ModLoader::Quilt => &[5, 4],
+		ModLoader::NeoForge => &[6],
+	}
+}
+
+impl CurseforgeResolver {
+	pub fn new(base_url: &str, api_key: String) -> anyhow::Result<Self> {
+		Ok(Self {
+			http: http_client()?,
+			base_url: base_url.trim_end_matches('/').into(),
+			api_key,
+		})
+	}
+
+	async fn get<T: for<'de> Deserialize<'de>>(
+		&self,
+		path: &str,
+		query: &[(&str, String)],
+	) -> anyhow::Result<CurseforgeResponse<T>> {
+		let response = self
+			.http
+			.get(format!("{}{path}", self.base_url))
+			.header("x-api-key", &self.api_key)
+			.query(query)
+			.send()
+			.await?;
+		if !response.status().is_success() {
+			anyhow::bail!("CurseForge responded to {path} with {}", response.status());
+		}
+		Ok(response.json().await?)
+	}
+
+	/// Mod list items can either use the project's numeric id or its slug
+	async fn find_mod(&self, mod_id: &str) -> anyhow::Result<CurseforgeMod> {
+		if let Ok(mod_id) = mod_id.parse::<u32>() {
+			return Ok(self.get(&format!("/v1/mods/{mod_id}"), &[]).await?.data);
+		}
+		let search_results: Vec<CurseforgeMod> = self
+			.get(
+				"/v1/mods/search",
+				&[
+					("gameId", MINECRAFT_GAME_ID.to_string()),
+					("classId", MODS_CLASS_ID.to_string()),
+					("slug", mod_id.into()),
+				],
+			)
+			.await?
+			.data;
+		search_results
+			.into_iter()
+			.find(|curseforge_mod| curseforge_mod.slug == mod_id)
+			.ok_or_else(|| anyhow::anyhow!("CurseForge has no mod called {mod_id}"))
+	}
+
+	async fn files(
+		&self,
+		mod_id: u32,
+		minecraft_version: &str,
+		loader_type: u8,
+	) -> anyhow::Result<Vec<CurseforgeFile>> {
+		let mut files = Vec::new();
+		loop {
+			let response: CurseforgeResponse<Vec<CurseforgeFile>> = self
+				.get(
+					&format!("/v1/mods/{mod_id}/files"),
+					&[
+						("gameVersion", minecraft_version.into()),
+						("modLoaderType", loader_type.to_string()),
+						("index", files.len().to_string()),
+						("pageSize", FILES_PAGE_SIZE.to_string()),
+					],
+				)
+				.await?;
+			files.extend(response.data);
+			match response.pagination {
+				Some(pagination)
+					if pagination.result_count > 0
+						&& pagination.index + pagination.result_count < pagination.total_count => {},
+				_ => return Ok(files),
+			}
+		}
+	}
+
+	/// Returns the newest file for the mod which works with the specified Minecraft version and loader, and is at least
+	/// as stable as the mod list item's channel
+	pub async fn resolve(
+		&self,
+		mod_list_item: &ModListItem,
+		minecraft_version: &str,
+		loader: ModLoader,
+	) -> anyhow::Result<ResolvedMod> {
+		if mod_list_item.repo != ModRepo::Curseforge {
+			anyhow::bail!("{} isn't a CurseForge mod", mod_list_item.id);
+		}
+		let curseforge_mod = self.find_mod(&mod_list_item.id).await?;
+		let mut files = Vec::new();
+		for loader_type in curseforge_loader_types(loader) {
+			files.extend(self.files(curseforge_mod.id, minecraft_version, *loader_type).await?);
+		}
+		let Some(file) = files
+			.into_iter()
+			.filter(|file| file.is_available && mod_list_item.channel.accepts(file.release_type.into()))
+			.max_by(|a, b| a.file_date.cmp(&b.file_date))
+		else {
+			anyhow::bail!(
+				"{} has no {:?} or more stable files for {loader} on Minecraft {minecraft_version}",
+				mod_list_item.id,
+				mod_list_item.channel
+			);
+		};
+		let Some(sha1) = file.sha1().map(String::from) else {
+			anyhow::bail!("{} file {} has no sha1 hash", mod_list_item.id, file.id);
+		};
+		let dependencies = file
+			.dependencies
+			.iter()
+			.filter(|dependency| dependency.relation_type == CurseforgeRelationType::RequiredDependency)
+			.map(|dependency| dependency.mod_id.to_string())
+			.collect();
+		Ok(ResolvedMod {
+			id: mod_list_item.id.clone(),
+			channel: mod_list_item.channel,
+			name: curseforge_mod.name,
+			version_name: file.display_name.clone(),
+			source: ModSource {
+				repo: ModRepo::Curseforge,
+				project_id: curseforge_mod.id.to_string(),
+				version_id: file.id.to_string(),
+			},
+			url: file.download_url.clone(),
+			distribution_allowed: curseforge_mod.allow_mod_distribution != Some(false),
+			side: file.side(),
+			supported_side: file.side(),
+			sha1,
+			sha512: None,
+			size: file.file_length,
+			curseforge_fingerprint: Some(file.file_fingerprint),
+			file_name: file.file_name,
+			dependencies,
+			required_by: Vec::new(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::{
+		extract::Query,
+		http::{HeaderMap, StatusCode},
+		routing::get,
+		Json, Router,
+	};
+	use serde_json::{json, Value};
+
+	use super::*;
+	use crate::test_utils::serve_mock;
+
+	fn check_api_key(headers: &HeaderMap) -> Result<(), StatusCode> {
+		match headers.get("x-api-key") {
+			Some(api_key) if api_key == "test-key" => Ok(()),
+			_ => Err(StatusCode::FORBIDDEN),
+		}
+	}
+
+	/// Serves a client-only mod whose author disallows distribution, with a newer beta on a second page of files
+	async fn mock_curseforge() -> String {
+		let router = Router::new()
+			.route(
+				"/v1/mods/search",
+				get(
+					|headers: HeaderMap, Query(query): Query<Vec<(String, String)>>| async move {
+						check_api_key(&headers)?;
+						assert!(query.contains(&("gameId".into(), "432".into())));
+						assert!(query.contains(&("slug".into(), "xaeros-minimap".into())));
+						Ok::<_, StatusCode>(Json(json!({
+							"data": [{ "id": 263420, "name": "Xaero's Minimap", "slug": "xaeros-minimap", "allowModDistribution": false }],
+						})))
+					},
+				),
+			)
+			.route(
+				"/v1/mods/263420/files",
+				get(
+					|headers: HeaderMap, Query(query): Query<Vec<(String, String)>>| async move {
+						check_api_key(&headers)?;
+						assert!(query.contains(&("gameVersion".into(), "1.20.1".into())));
+						assert!(query.contains(&("modLoaderType".into(), "4".into())));
+						let file = |id: u32, release_type: u8, file_date: &str| {
+							json!({
+								"id": id,
+								"displayName": format!("Xaero's Minimap {id}"),
+								"fileName": format!("Xaeros_Minimap_{id}.jar"),
+								"releaseType": release_type,
+								"fileDate": file_date,
+								"downloadUrl": format!("https://edge.forgecdn.net/files/{id}/Xaeros_Minimap_{id}.jar"),
+								"fileLength": 456,
+								"fileFingerprint": 789,
+								"hashes": [{ "value": "md5", "algo": 2 }, { "value": format!("{id}-sha1"), "algo": 1 }],
+								"gameVersions": ["1.20.1", "Fabric", "Client"],
+							})
+						};
+						let index = query
+							.iter()
+							.find(|(key, _)| key == "index")
+							.map(|(_, value)| value.as_str());
+						let (files, index) = if index == Some("0") {
+							(vec![file(4_000_001, 1, "2024-01-01T00:00:00Z")], 0)
+						} else {
+							(vec![file(4_000_002, 2, "2024-02-01T00:00:00Z")], 1)
+						};
+						Ok::<_, StatusCode>(Json(json!({
+							"data": Value::Array(files),
+							"pagination": { "index": index, "pageSize": 50, "resultCount": 1, "totalCount": 2 },
+						})))
+					},
+				),
+			);
+		serve_mock(router).await
+	}
+
+	fn minimap(channel: ModRepoChannel) -> ModListItem {
+		ModListItem {
+			id: "xaeros-minimap".into(),
+			repo: ModRepo::Curseforge,
+			channel,
+		}
+	}
+
+	#[tokio::test]
+	async fn resolves_newest_file_in_channel() {
+		let resolver = CurseforgeResolver::new(&mock_curseforge().await, "test-key".into()).unwrap();
+
+		let release = resolver
+			.resolve(&minimap(ModRepoChannel::Release), "1.20.1", ModLoader::Fabric)
+			.await
+			.unwrap();
+		assert_eq!(release.source.project_id, "263420");
+		assert_eq!(release.source.version_id, "4000001");
+		assert_eq!(release.sha1, "4000001-sha1");
+		assert_eq!(release.curseforge_fingerprint, Some(789));
+		assert_eq!(release.side, PackwizModSide::Client);
+		assert!(!release.distribution_allowed);
+		assert!(release.needs_manual_download());
+		assert_eq!(
+			release.url.as_deref(),
+			Some("https://edge.forgecdn.net/files/4000001/Xaeros_Minimap_4000001.jar")
+		);
+
+		let beta = resolver
+			.resolve(&minimap(ModRepoChannel::Beta), "1.20.1", ModLoader::Fabric)
+			.await
+			.unwrap();
+		assert_eq!(beta.file_name, "Xaeros_Minimap_4000002.jar");
+	}
+
+	#[tokio::test]
+	async fn wrong_api_keys_are_errors() {
+		let resolver = CurseforgeResolver::new(&mock_curseforge().await, "wrong-key".into()).unwrap();
+		assert!(resolver
+			.resolve(&minimap(ModRepoChannel::Release), "1.20.1", ModLoader::Fabric)
+			.await
+			.is_err());
+	}
+}
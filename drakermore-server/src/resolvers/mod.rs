@@ -0,0 +1,389 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::schemas::{DrakermoreModConfig, ModListItem, ModLoader, ModRepo, ModRepoChannel, ModSource, PackwizModSide};
+
+pub mod curseforge;
+pub mod modrinth;
+
+use curseforge::CurseforgeResolver;
+use modrinth::ModrinthResolver;
+
+/// Identifies us to the mod repos, Modrinth asks for this to be unique to the project
+const USER_AGENT: &str = concat!("drakermore-server/", env!("CARGO_PKG_VERSION"));
+
+/// The file a mod list item should be downloaded from, as decided by its repo
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedMod {
+	/// The mod list item's id, or the id the mod was asked for by the mods which need it
+	pub id: String,
+	/// Dependencies are looked for in the same channel as the mod which needs them
+	#[serde(skip)]
+	pub channel: ModRepoChannel,
+	/// The project's display name
+	pub name: String,
+	pub version_name: String,
+	pub source: ModSource,
+	pub file_name: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	/// Whether the author lets the file be downloaded by anything but their repo's own launcher, the file has to be
+	/// downloaded by hand if they don't
+	pub distribution_allowed: bool,
+	/// hex-encoded sha1 hash of the file, every repo gives us this one
+	pub sha1: String,
+	/// hex-encoded sha512 hash of the file, if the repo gives us one
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sha512: Option<String>,
+	pub size: u64,
+	/// The realm the file should be downloaded to
+	pub side: PackwizModSide,
+	/// The sides the repo says the file works on, which dependencies' sides get narrowed down from
+	#[serde(skip)]
+	pub supported_side: PackwizModSide,
+	/// CurseForge's murmur2-based hash of the file
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub curseforge_fingerprint: Option<u32>,
+	/// Project ids of the mods this one can't run without, in the same repo
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub dependencies: Vec<String>,
+	/// Ids of the mods which needed this one, empty if the mod is in the mod list itself
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub required_by: Vec<String>,
+}
+impl ResolvedMod {
+	/// Whether the mod was only added because other mods need it
+	pub fn is_implicit(&self) -> bool {
+		!self.required_by.is_empty()
+	}
+	/// Whether resolving the mod list item would give us this mod
+	fn matches(&self, mod_list_item: &ModListItem) -> bool {
+		self.source.repo == mod_list_item.repo
+			&& (self.id == mod_list_item.id || self.source.project_id == mod_list_item.id)
+	}
+	/// Whether the file has to be downloaded by hand, either because its author wants it that way or because the repo
+	/// didn't give us a URL for it
+	pub fn needs_manual_download(&self) -> bool {
+		!self.distribution_allowed || self.url.is_none()
+	}
+	/// The jar the config says was downloaded by hand for this mod, if there is one
+	pub fn manual_download<'a>(&self, modpack: &'a DrakermoreModConfig) -> Option<&'a Path> {
+		[&self.id, &self.source.project_id]
+			.into_iter()
+			.find_map(|id| modpack.manual_downloads.get(id))
+			.map(PathBuf::as_path)
+	}
+	/// Explicit mods stay explicit even if other mods need them too
+	fn add_required_by(&mut self, required_by: String) {
+		if self.is_implicit() && self.id != required_by && !self.required_by.contains(&required_by) {
+			self.required_by.push(required_by);
+		}
+	}
+	pub fn dependency_requests(&self) -> impl Iterator<Item = DependencyRequest> + '_ {
+		self.dependencies.iter().map(|project_id| DependencyRequest {
+			mod_list_item: ModListItem {
+				id: project_id.clone(),
+				repo: self.source.repo,
+				channel: self.channel,
+			},
+			required_by: self.id.clone(),
+			guessed: false,
+		})
+	}
+}
+
+/// A mod which another mod needs, and which might not be in the mod list
+#[derive(Debug, Clone)]
+pub struct DependencyRequest {
+	pub mod_list_item: ModListItem,
+	/// The id of the mod which needs it
+	pub required_by: String,
+	/// True if the id came from a jar's `fabric.mod.json` rather than from the mod repo, so the repo might not know it
+	pub guessed: bool,
+}
+
+/// What the `resolve` command prints
+#[derive(Debug, Default, Serialize)]
+pub struct ResolvedModList {
+	pub mods: Vec<ResolvedMod>,
+}
+
+/// Picks the right resolver for each mod list item's repo
+#[derive(Debug, Clone)]
+pub struct ModResolver {
+	pub modrinth: ModrinthResolver,
+	/// The CurseForge API can't be used without an API key
+	pub curseforge: Option<CurseforgeResolver>,
+}
+impl ModResolver {
+	pub async fn resolve(
+		&self,
+		mod_list_item: &ModListItem,
+		minecraft_version: &str,
+		loader: ModLoader,
+	) -> anyhow::Result<ResolvedMod> {
+		match mod_list_item.repo {
+			ModRepo::Modrinth => self.modrinth.resolve(mod_list_item, minecraft_version, loader).await,
+			ModRepo::Curseforge => match &self.curseforge {
+				Some(curseforge) => curseforge.resolve(mod_list_item, minecraft_version, loader).await,
+				None => anyhow::bail!(
+					"{} is a CurseForge mod, which needs a CurseForge API key",
+					mod_list_item.id
+				),
+			},
+		}
+	}
+
+	/// Resolves every mod list item, and every mod they need which isn't in the mod list
+	pub async fn resolve_mod_list(&self, modpack: &DrakermoreModConfig) -> anyhow::Result<Vec<ResolvedMod>> {
+		let mut resolved_mods = Vec::with_capacity(modpack.mod_list.len());
+		for mod_list_item in modpack.mod_list.iter() {
+			let resolved_mod = self
+				.resolve(mod_list_item, &modpack.minecraft_version, modpack.loader)
+				.await
+				.map_err(|err| anyhow::anyhow!("couldn't resolve {}: {err}", mod_list_item.id))?;
+			resolved_mods.push(resolved_mod);
+		}
+		let dependency_requests = resolved_mods
+			.iter()
+			.flat_map(ResolvedMod::dependency_requests)
+			.collect();
+		self.add_dependencies(&mut resolved_mods, dependency_requests, modpack)
+			.await?;
+		Ok(resolved_mods)
+	}
+
+	/// Resolves the requested mods, and the mods they need in turn, unless they're already in `resolved_mods`. Then
+	/// every mod which is only there because other mods need it gets its side narrowed down to the sides of those mods,
+	/// including mods added by earlier calls, since the mods which need them might be on another side.
+	pub async fn add_dependencies(
+		&self,
+		resolved_mods: &mut Vec<ResolvedMod>,
+		mut dependency_requests: Vec<DependencyRequest>,
+		modpack: &DrakermoreModConfig,
+	) -> anyhow::Result<()> {
+		dependency_requests.reverse();
+		while let Some(request) = dependency_requests.pop() {
+			if let Some(existing_mod) = resolved_mods
+				.iter_mut()
+				.find(|resolved_mod| resolved_mod.matches(&request.mod_list_item))
+			{
+				existing_mod.add_required_by(request.required_by);
+				continue;
+			}
+			let mut resolved_mod = match self
+				.resolve(&request.mod_list_item, &modpack.minecraft_version, modpack.loader)
+				.await
+			{
+				Ok(resolved_mod) => resolved_mod,
+				Err(err) if request.guessed => {
+					tracing::warn!(
+						"{} needs {}, which couldn't be found on {:?}: {err}",
+						request.required_by,
+						request.mod_list_item.id,
+						request.mod_list_item.repo
+					);
+					continue;
+				},
+				Err(err) => anyhow::bail!(
+					"couldn't resolve {}, which {} needs: {err}",
+					request.mod_list_item.id,
+					request.required_by
+				),
+			};
+			// Slugs only turn into project ids once they're resolved
+			if let Some(existing_mod) = resolved_mods.iter_mut().find(|existing_mod| {
+				existing_mod.source.repo == resolved_mod.source.repo
+					&& existing_mod.source.project_id == resolved_mod.source.project_id
+			}) {
+				existing_mod.add_required_by(request.required_by);
+				continue;
+			}
+			tracing::info!("Adding {}, which {} needs", resolved_mod.name, request.required_by);
+			resolved_mod.required_by.push(request.required_by);
+			dependency_requests.extend(resolved_mod.dependency_requests());
+			resolved_mods.push(resolved_mod);
+		}
+		narrow_dependency_sides(resolved_mods);
+		Ok(())
+	}
+}
+
+/// Dependencies only go to the sides the mods which need them go to, as long as the dependencies support those sides
+fn narrow_dependency_sides(resolved_mods: &mut [ResolvedMod]) {
+	// Sides only ever get narrower, except when a mod ends up on a side its dependency doesn't support, so this
+	// settles quickly. The limit is just in case it doesn't.
+	for _ in 0..=resolved_mods.len() {
+		let mut changed = false;
+		for mod_index in 0..resolved_mods.len() {
+			let needed_side = resolved_mods[mod_index]
+				.required_by
+				.iter()
+				.filter_map(|required_by| {
+					resolved_mods
+						.iter()
+						.find(|resolved_mod| &resolved_mod.id == required_by)
+				})
+				.map(|resolved_mod| resolved_mod.side)
+				.reduce(PackwizModSide::union);
+			let Some(needed_side) = needed_side else {
+				continue;
+			};
+			let supported_side = resolved_mods[mod_index].supported_side;
+			let side = supported_side.intersection(needed_side).unwrap_or(supported_side);
+			if resolved_mods[mod_index].side != side {
+				resolved_mods[mod_index].side = side;
+				changed = true;
+			}
+		}
+		if !changed {
+			break;
+		}
+	}
+}
+
+pub fn http_client() -> anyhow::Result<reqwest::Client> {
+	Ok(reqwest::Client::builder().user_agent(USER_AGENT).build()?)
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+	use serde_json::{json, Value};
+
+	use super::*;
+	use crate::test_utils::{serve_mock, test_config};
+
+	/// Serves a client-only mod which needs a library, which in turn needs Fabric API, and a server-only mod which needs
+	/// Fabric API too
+	async fn mock_modrinth() -> String {
+		fn project(slug_or_id: &str) -> Option<(&'static str, &'static str, &'static str, Value)> {
+			let required = |project_id: &str| json!({ "project_id": project_id, "dependency_type": "required" });
+			match slug_or_id {
+				"iris" | "YL57xq9U" => Some((
+					"YL57xq9U",
+					"required",
+					"unsupported",
+					json!([required("AANobbMI"), { "project_id": "BVzZfTc1", "dependency_type": "incompatible" }]),
+				)),
+				"sodium" | "AANobbMI" => Some(("AANobbMI", "required", "optional", json!([required("P7dR8mSH")]))),
+				"fabric-api" | "P7dR8mSH" => Some(("P7dR8mSH", "required", "required", json!([]))),
+				"ledger" | "LVN9ygNV" => Some(("LVN9ygNV", "unsupported", "required", json!([required("P7dR8mSH")]))),
+				_ => None,
+			}
+		}
+		let router = Router::new()
+			.route(
+				"/project/:project",
+				get(|Path(slug_or_id): Path<String>| async move {
+					let (id, client_side, server_side, _) = project(&slug_or_id).ok_or(StatusCode::NOT_FOUND)?;
+					Ok::<_, StatusCode>(Json(json!({
+						"id": id,
+						"title": slug_or_id,
+						"client_side": client_side,
+						"server_side": server_side,
+					})))
+				}),
+			)
+			.route(
+				"/project/:project/version",
+				get(|Path(project_id): Path<String>| async move {
+					let (_, _, _, dependencies) = project(&project_id).ok_or(StatusCode::NOT_FOUND)?;
+					Ok::<_, StatusCode>(Json(json!([{
+						"id": format!("{project_id}-version"),
+						"version_number": "1.0",
+						"version_type": "release",
+						"date_published": "2024-01-01T00:00:00Z",
+						"files": [{
+							"hashes": { "sha1": "sha1", "sha512": "sha512" },
+							"url": format!("https://cdn.modrinth.com/{project_id}.jar"),
+							"filename": format!("{project_id}.jar"),
+							"primary": true,
+							"size": 1,
+						}],
+						"dependencies": dependencies,
+					}])))
+				}),
+			);
+		serve_mock(router).await
+	}
+
+	#[tokio::test]
+	async fn required_dependencies_are_added_with_the_sides_they_are_needed_on() {
+		let mod_resolver = ModResolver {
+			modrinth: ModrinthResolver::new(&mock_modrinth().await).unwrap(),
+			curseforge: None,
+		};
+		let modpack = test_config(
+			r#"
+mod_list = [
+	{ id = "iris", repo = "modrinth", channel = "release" },
+	{ id = "fabric-api", repo = "modrinth", channel = "release" },
+]
+"#,
+		);
+
+		let resolved_mods = mod_resolver.resolve_mod_list(&modpack).await.unwrap();
+		let summary: Vec<_> = resolved_mods
+			.iter()
+			.map(|resolved_mod| {
+				(
+					resolved_mod.id.as_str(),
+					resolved_mod.side,
+					resolved_mod.required_by.clone(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			summary,
+			[
+				("iris", PackwizModSide::Client, vec![]),
+				("fabric-api", PackwizModSide::Both, vec![]),
+				("AANobbMI", PackwizModSide::Client, vec!["iris".to_string()]),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn dependency_sides_widen_when_a_later_round_needs_them_on_another_side() {
+		let mod_resolver = ModResolver {
+			modrinth: ModrinthResolver::new(&mock_modrinth().await).unwrap(),
+			curseforge: None,
+		};
+		let modpack = test_config(r#"mod_list = [{ id = "iris", repo = "modrinth", channel = "release" }]"#);
+		let mut resolved_mods = mod_resolver.resolve_mod_list(&modpack).await.unwrap();
+		let fabric_api = |resolved_mods: &[ResolvedMod]| {
+			let fabric_api = resolved_mods
+				.iter()
+				.find(|resolved_mod| resolved_mod.id == "P7dR8mSH")
+				.unwrap();
+			(fabric_api.side, fabric_api.required_by.clone())
+		};
+		assert_eq!(
+			fabric_api(&resolved_mods),
+			(PackwizModSide::Client, vec!["AANobbMI".to_string()])
+		);
+
+		// Like sync finding a server-only mod through a fabric.mod.json, after Fabric API was narrowed down
+		let ledger = ModListItem {
+			id: "ledger".into(),
+			repo: ModRepo::Modrinth,
+			channel: ModRepoChannel::Release,
+		};
+		let ledger = mod_resolver
+			.resolve(&ledger, &modpack.minecraft_version, modpack.loader)
+			.await
+			.unwrap();
+		let dependency_requests = ledger.dependency_requests().collect();
+		resolved_mods.push(ledger);
+		mod_resolver
+			.add_dependencies(&mut resolved_mods, dependency_requests, &modpack)
+			.await
+			.unwrap();
+		assert_eq!(
+			fabric_api(&resolved_mods),
+			(PackwizModSide::Both, vec!["AANobbMI".to_string(), "ledger".to_string()])
+		);
+	}
+}
@@ -0,0 +1,275 @@
+use serde::Deserialize;
+
+use super::{http_client, ResolvedMod};
+use crate::schemas::{ModListItem, ModLoader, ModRepo, ModRepoChannel, ModSource, PackwizModSide};
+
+pub const MODRINTH_API_URL: &str = "https://api.modrinth.com/v2";
+
+/// Finds mod files through the Modrinth v2 API, see https://docs.modrinth.com/api/
+#[derive(Debug, Clone)]
+pub struct ModrinthResolver {
+	http: reqwest::Client,
+	/// Without a trailing slash, e.g. `MODRINTH_API_URL`
+	base_url: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+enum ModrinthSideSupport {
+	#[serde(rename = "required")]
+	Required,
+	#[serde(rename = "optional")]
+	Optional,
+	#[serde(rename = "unsupported")]
+	Unsupported,
+	#[serde(other)]
+	Unknown,
+}
+#[derive(Debug, Deserialize)]
+struct ModrinthProject {
+	id: String,
+	title: String,
+	client_side: ModrinthSideSupport,
+	server_side: ModrinthSideSupport,
+}
+impl ModrinthProject {
+	/// Mods which don't say what they support get installed everywhere, just to be safe
+	fn side(&self) -> PackwizModSide {
+		match (self.client_side, self.server_side) {
+			(ModrinthSideSupport::Unsupported, ModrinthSideSupport::Unsupported) => PackwizModSide::Both,
+			(ModrinthSideSupport::Unsupported, _) => PackwizModSide::Server,
+			(_, ModrinthSideSupport::Unsupported) => PackwizModSide::Client,
+			_ => PackwizModSide::Both,
+		}
+	}
+}
+#[derive(Debug, Deserialize)]
+struct ModrinthVersion {
+	id: String,
+	version_number: String,
+	version_type: ModRepoChannel,
+	/// ISO 8601, so comparing these as strings also compares them chronologically
+	date_published: String,
+	files: Vec<ModrinthVersionFile>,
+	#[serde(default)]
+	dependencies: Vec<ModrinthDependency>,
+}
+#[derive(Debug, Deserialize)]
+struct ModrinthDependency {
+	/// Null when the dependency is on a specific version, or on a file which isn't on Modrinth
+	project_id: Option<String>,
+	dependency_type: ModrinthDependencyType,
+}
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+enum ModrinthDependencyType {
+	#[serde(rename = "required")]
+	Required,
+	#[serde(rename = "optional")]
+	Optional,
+	#[serde(rename = "incompatible")]
+	Incompatible,
+	/// Already included in the mod's jar
+	#[serde(rename = "embedded")]
+	Embedded,
+	#[serde(other)]
+	Unknown,
+}
+#[derive(Debug, Deserialize)]
+struct ModrinthVersionFile {
+	hashes: ModrinthFileHashes,
+	url: String,
+	filename: String,
+	primary: bool,
+	size: u64,
+}
+#[derive(Debug, Deserialize)]
+struct ModrinthFileHashes {
+	sha1: String,
+	sha512: String,
+}
+
+impl ModrinthResolver {
+	pub fn new(base_url: &str) -> anyhow::Result<Self> {
+		Ok(Self {
+			http: http_client()?,
+			base_url: base_url.trim_end_matches('/').into(),
+		})
+	}
+
+	async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str, query: &[(&str, String)]) -> anyhow::Result<T> {
+		let response = self
+			.http
+			.get(format!("{}{path}", self.base_url))
+			.query(query)
+			.send()
+			.await?;
+		if !response.status().is_success() {
+			anyhow::bail!("Modrinth responded to {path} with {}", response.status());
+		}
+		Ok(response.json().await?)
+	}
+
+	/// Returns the newest file for the mod which works with the specified Minecraft version and loader, and is at least
+	/// as stable as the mod list item's channel
+	pub async fn resolve(
+		&self,
+		mod_list_item: &ModListItem,
+		minecraft_version: &str,
+		loader: ModLoader,
+	) -> anyhow::Result<ResolvedMod> {
+		if mod_list_item.repo != ModRepo::Modrinth {
+			anyhow::bail!("{} isn't a Modrinth mod", mod_list_item.id);
+		}
+		let project: ModrinthProject = self.get(&format!("/project/{}", mod_list_item.id), &[]).await?;
+		let mut loaders = vec![loader.to_string()];
+		// Quilt can load Fabric mods
+		if loader == ModLoader::Quilt {
+			loaders.push(ModLoader::Fabric.to_string());
+		}
+		let versions: Vec<ModrinthVersion> = self
+			.get(
+				&format!("/project/{}/version", project.id),
+				&[
+					("loaders", serde_json::to_string(&loaders)?),
+					("game_versions", serde_json::to_string(&[minecraft_version])?),
+				],
+			)
+			.await?;
+		let Some(version) = versions
+			.into_iter()
+			.filter(|version| mod_list_item.channel.accepts(version.version_type))
+			.max_by(|a, b| a.date_published.cmp(&b.date_published))
+		else {
+			anyhow::bail!(
+				"{} has no {:?} or more stable versions for {loader} on Minecraft {minecraft_version}",
+				mod_list_item.id,
+				mod_list_item.channel
+			);
+		};
+		let Some(file) = version.files.iter().find(|file| file.primary).or(version.files.first()) else {
+			anyhow::bail!("{} version {} has no files", mod_list_item.id, version.version_number);
+		};
+		let dependencies = version
+			.dependencies
+			.iter()
+			.filter(|dependency| dependency.dependency_type == ModrinthDependencyType::Required)
+			.filter_map(|dependency| dependency.project_id.clone())
+			.collect();
+		Ok(ResolvedMod {
+			id: mod_list_item.id.clone(),
+			channel: mod_list_item.channel,
+			side: project.side(),
+			supported_side: project.side(),
+			name: project.title,
+			version_name: version.version_number.clone(),
+			source: ModSource {
+				repo: ModRepo::Modrinth,
+				project_id: project.id,
+				version_id: version.id.clone(),
+			},
+			file_name: file.filename.clone(),
+			url: Some(file.url.clone()),
+			distribution_allowed: true,
+			sha1: file.hashes.sha1.clone(),
+			sha512: Some(file.hashes.sha512.clone()),
+			size: file.size,
+			curseforge_fingerprint: None,
+			dependencies,
+			required_by: Vec::new(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::{extract::Query, routing::get, Json, Router};
+	use serde_json::{json, Value};
+
+	use super::*;
+	use crate::test_utils::serve_mock;
+
+	/// Serves a client-only project with a newer beta and an older release
+	async fn mock_modrinth() -> String {
+		let router = Router::new()
+			.route(
+				"/project/sodium",
+				get(|| async {
+					Json(json!({
+						"id": "AANobbMI",
+						"title": "Sodium",
+						"client_side": "required",
+						"server_side": "unsupported",
+					}))
+				}),
+			)
+			.route(
+				"/project/AANobbMI/version",
+				get(|Query(query): Query<Vec<(String, String)>>| async move {
+					assert!(query.contains(&("loaders".into(), r#"["fabric"]"#.into())));
+					assert!(query.contains(&("game_versions".into(), r#"["1.20.1"]"#.into())));
+					let file = |file_name: &str, primary: bool| {
+						json!({
+							"hashes": { "sha1": format!("{file_name}-sha1"), "sha512": format!("{file_name}-sha512") },
+							"url": format!("https://cdn.modrinth.com/data/AANobbMI/versions/{file_name}"),
+							"filename": file_name,
+							"primary": primary,
+							"size": 123,
+						})
+					};
+					Json(Value::Array(vec![
+						json!({
+							"id": "beta",
+							"version_number": "0.5.9-beta",
+							"version_type": "beta",
+							"date_published": "2024-02-01T00:00:00Z",
+							"files": [file("sodium-0.5.9.jar", true)],
+						}),
+						json!({
+							"id": "release",
+							"version_number": "0.5.8",
+							"version_type": "release",
+							"date_published": "2024-01-01T00:00:00Z",
+							"files": [file("sodium-0.5.8-sources.jar", false), file("sodium-0.5.8.jar", true)],
+						}),
+					]))
+				}),
+			);
+		format!("{}/", serve_mock(router).await)
+	}
+
+	fn sodium(channel: ModRepoChannel) -> ModListItem {
+		ModListItem {
+			id: "sodium".into(),
+			repo: ModRepo::Modrinth,
+			channel,
+		}
+	}
+
+	#[tokio::test]
+	async fn resolves_newest_primary_file_in_channel() {
+		let resolver = ModrinthResolver::new(&mock_modrinth().await).unwrap();
+
+		let release = resolver
+			.resolve(&sodium(ModRepoChannel::Release), "1.20.1", ModLoader::Fabric)
+			.await
+			.unwrap();
+		assert_eq!(release.file_name, "sodium-0.5.8.jar");
+		assert_eq!(release.source.project_id, "AANobbMI");
+		assert_eq!(release.source.version_id, "release");
+		assert_eq!(release.sha1, "sodium-0.5.8.jar-sha1");
+		assert_eq!(release.side, PackwizModSide::Client);
+
+		let beta = resolver
+			.resolve(&sodium(ModRepoChannel::Beta), "1.20.1", ModLoader::Fabric)
+			.await
+			.unwrap();
+		assert_eq!(beta.file_name, "sodium-0.5.9.jar");
+	}
+
+	#[tokio::test]
+	async fn missing_projects_are_errors() {
+		let resolver = ModrinthResolver::new(&mock_modrinth().await).unwrap();
+		let mut missing = sodium(ModRepoChannel::Release);
+		missing.id = "missing".into();
+		assert!(resolver.resolve(&missing, "1.20.1", ModLoader::Fabric).await.is_err());
+	}
+}
@@ -1,30 +1,21 @@
 use std::{
-	io::{Cursor, Error as IoError, ErrorKind as IoErrorKind},
-	ops::{Deref, DerefMut},
+	io::{Error as IoError, ErrorKind as IoErrorKind, Write},
+	time::SystemTime,
 };
 
 use axum::{
-	http::{header, HeaderName, HeaderValue, StatusCode},
+	body::Body,
+	http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
 	response::{IntoResponse, Response},
 };
 use bytes::Bytes;
 use lazy_regex::regex_replace_all;
-use zip::ZipWriter;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use zip::{write::StreamWriter, ZipWriter};
+
+use crate::{admin::AdminError, safe_path::SafePathError};
 
-pub fn ok_or_500_response<T: IntoResponse, E: std::error::Error>(result: Result<T, E>) -> Response {
-	match result {
-		Ok(response) => response.into_response(),
-		Err(err) => (
-			StatusCode::INTERNAL_SERVER_ERROR,
-			[(
-				header::CONTENT_TYPE,
-				HeaderValue::from_static(mime::TEXT_PLAIN_UTF_8.as_ref()),
-			)],
-			err.to_string(),
-		)
-			.into_response(),
-	}
-}
 // I discovered that https://docs.rs/axum/latest/axum/response/type.Result.html exists, whoops!
 pub fn ok_or_anyhow_response<T: IntoResponse>(result: Result<T, anyhow::Error>) -> Response {
 	let headers = [(
@@ -33,6 +24,22 @@ pub fn ok_or_anyhow_response<T: IntoResponse>(result: Result<T, anyhow::Error>)
 	)];
 	match result {
 		Ok(response) => response.into_response(),
+		Err(err) if err.is::<SafePathError>() => match err.downcast_ref::<SafePathError>() {
+			Some(path_err @ SafePathError::NotFound(_)) => {
+				(StatusCode::NOT_FOUND, headers, path_err.to_string()).into_response()
+			},
+			Some(path_err @ SafePathError::Forbidden(_)) => {
+				(StatusCode::FORBIDDEN, headers, path_err.to_string()).into_response()
+			},
+			Some(path_err @ SafePathError::NotAJar(_)) => {
+				(StatusCode::BAD_REQUEST, headers, path_err.to_string()).into_response()
+			},
+			_ => (StatusCode::INTERNAL_SERVER_ERROR, headers, format!("{:?}", err)).into_response(),
+		},
+		Err(err) if err.is::<AdminError>() => match err.downcast_ref::<AdminError>() {
+			Some(admin_err) => (admin_err.status_code(), headers, admin_err.to_string()).into_response(),
+			None => (StatusCode::INTERNAL_SERVER_ERROR, headers, format!("{:?}", err)).into_response(),
+		},
 		Err(err) => match err.downcast_ref::<IoError>() {
 			Some(io_err) if io_err.kind() == IoErrorKind::NotFound => {
 				(StatusCode::NOT_FOUND, headers, io_err.to_string()).into_response()
@@ -44,44 +51,140 @@ pub fn ok_or_anyhow_response<T: IntoResponse>(result: Result<T, anyhow::Error>)
 		},
 	}
 }
+/// Responds with a 304 instead of `response` if the request's `If-None-Match` or `If-Modified-Since` headers say the
+/// client already has it. `etag` is expected to be a content hash.
+pub fn conditional_response(
+	request_headers: &HeaderMap,
+	etag: &str,
+	last_modified: SystemTime,
+	response: impl IntoResponse,
+) -> Response {
+	let etag = format!("\"{etag}\"");
+	// HTTP dates don't do fractions of a second
+	let last_modified = httpdate::HttpDate::from(last_modified);
+	let not_modified = match request_headers.get(header::IF_NONE_MATCH) {
+		// If-Modified-Since is ignored when If-None-Match is present, see RFC 9110 section 13.1.3
+		Some(if_none_match) => if_none_match.to_str().is_ok_and(|if_none_match| {
+			if_none_match
+				.split(',')
+				.map(str::trim)
+				.any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+		}),
+		None => request_headers
+			.get(header::IF_MODIFIED_SINCE)
+			.and_then(|if_modified_since| if_modified_since.to_str().ok()?.parse::<httpdate::HttpDate>().ok())
+			.is_some_and(|if_modified_since| last_modified <= if_modified_since),
+	};
+	let headers = [
+		(
+			header::ETAG,
+			HeaderValue::from_str(&etag).expect("etag should be a hex-encoded hash"),
+		),
+		(
+			header::LAST_MODIFIED,
+			HeaderValue::from_str(&last_modified.to_string()).expect("HTTP dates are always valid header values"),
+		),
+	];
+	if not_modified {
+		(StatusCode::NOT_MODIFIED, headers).into_response()
+	} else {
+		(headers, response).into_response()
+	}
+}
+/// How many bytes `ChannelWriter` collects before sending them off to the client
+const ZIP_RESPONSE_CHUNK_SIZE: usize = 64 * 1024;
+/// How many chunks can be waiting to be sent before the zip writer has to wait for the client to catch up
+const ZIP_RESPONSE_CHUNK_BACKLOG: usize = 4;
+
+pub type ZipResponseWriter = ZipWriter<StreamWriter<ChannelWriter>>;
+type ZipResponseEntries = Box<dyn FnOnce(&mut ZipResponseWriter) -> anyhow::Result<()> + Send>;
+
+/// A zip file which is sent to the client while it's being written, so only a few chunks are ever held in memory
 pub struct ZipResponse {
 	file_name: String,
-	inner: ZipWriter<Cursor<Vec<u8>>>,
+	content_type: &'static str,
+	write_entries: ZipResponseEntries,
 }
 impl ZipResponse {
-	pub fn new(file_name: String) -> Self {
+	/// `write_entries` runs on a blocking thread once the response is sent, so it can read files synchronously. Since
+	/// the status code has been sent by then, any errors it returns will abort the download.
+	pub fn new(
+		file_name: String,
+		write_entries: impl FnOnce(&mut ZipResponseWriter) -> anyhow::Result<()> + Send + 'static,
+	) -> Self {
 		Self {
 			file_name,
-			inner: ZipWriter::new(Cursor::new(Vec::with_capacity(1024))),
+			content_type: "application/zip",
+			write_entries: Box::new(write_entries),
 		}
 	}
+	/// For zip-based formats which have their own mime type
+	pub fn with_content_type(mut self, content_type: &'static str) -> Self {
+		self.content_type = content_type;
+		self
+	}
 }
-impl Deref for ZipResponse {
-	type Target = ZipWriter<Cursor<Vec<u8>>>;
-	fn deref(&self) -> &Self::Target {
-		&self.inner
+impl IntoResponse for ZipResponse {
+	fn into_response(self) -> Response {
+		let (chunk_sender, chunk_receiver) = mpsc::channel(ZIP_RESPONSE_CHUNK_BACKLOG);
+		let error_sender = chunk_sender.clone();
+		let file_name = self.file_name;
+		let write_entries = self.write_entries;
+		let zip_file_name = file_name.clone();
+		tokio::task::spawn_blocking(move || {
+			let mut zip = ZipWriter::new_stream(ChannelWriter::new(chunk_sender));
+			let result = write_entries(&mut zip)
+				.and_then(|_| Ok(zip.finish()?.into_inner().flush()?))
+				.map_err(|err| {
+					tracing::error!("Failed to write {zip_file_name}: {err:?}");
+					IoError::other(err)
+				});
+			if let Err(err) = result {
+				// Nothing to do if the client has already gone away
+				let _ = error_sender.blocking_send(Err(err));
+			}
+		});
+		(
+			[
+				(header::CONTENT_TYPE, HeaderValue::from_static(self.content_type)),
+				download_file_name_header(file_name.as_str()),
+			],
+			Body::from_stream(ReceiverStream::new(chunk_receiver)),
+		)
+			.into_response()
 	}
 }
-impl DerefMut for ZipResponse {
-	fn deref_mut(&mut self) -> &mut Self::Target {
-		&mut self.inner
+
+/// Sends everything written to it down a channel in `ZIP_RESPONSE_CHUNK_SIZE` chunks
+pub struct ChannelWriter {
+	buffer: Vec<u8>,
+	chunk_sender: mpsc::Sender<Result<Bytes, IoError>>,
+}
+impl ChannelWriter {
+	fn new(chunk_sender: mpsc::Sender<Result<Bytes, IoError>>) -> Self {
+		Self {
+			buffer: Vec::with_capacity(ZIP_RESPONSE_CHUNK_SIZE),
+			chunk_sender,
+		}
 	}
 }
-impl IntoResponse for ZipResponse {
-	fn into_response(self) -> Response {
-		// Use a small initial capacity of 128 bytes like serde_json::to_vec
-		// https://docs.rs/serde_json/1.0.82/src/serde_json/ser.rs.html#2189'
-		let filename = self.file_name;
-		ok_or_500_response(self.inner.finish().map(|zip_bytes| {
-			(
-				[
-					(header::CONTENT_TYPE, HeaderValue::from_static("application/zip")),
-					download_file_name_header(filename.as_str()),
-				],
-				Bytes::from(zip_bytes.into_inner()),
-			)
-				.into_response()
-		}))
+impl Write for ChannelWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		let len = buf.len().min(ZIP_RESPONSE_CHUNK_SIZE - self.buffer.len());
+		self.buffer.extend_from_slice(&buf[..len]);
+		if self.buffer.len() >= ZIP_RESPONSE_CHUNK_SIZE {
+			self.flush()?;
+		}
+		Ok(len)
+	}
+	fn flush(&mut self) -> std::io::Result<()> {
+		if self.buffer.is_empty() {
+			return Ok(());
+		}
+		let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(ZIP_RESPONSE_CHUNK_SIZE));
+		self.chunk_sender
+			.blocking_send(Ok(chunk.into()))
+			.map_err(|_| IoError::new(IoErrorKind::BrokenPipe, "client stopped receiving the zip file"))
 	}
 }
 
@@ -97,3 +200,74 @@ pub fn download_file_name_header(filename: &str) -> (HeaderName, HeaderValue) {
 		.expect("filename should being safe should already have been validated"),
 	)
 }
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Cursor, Read};
+
+	use futures::StreamExt;
+	use zip::{write::SimpleFileOptions, ZipArchive};
+
+	use super::*;
+
+	/// Bigger than a few chunks, and doesn't compress down to less than one
+	fn big_entry() -> Vec<u8> {
+		(0..(3 * ZIP_RESPONSE_CHUNK_SIZE + 123))
+			.map(|i| (i * 7 % 251) as u8)
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn zip_responses_stream_a_valid_zip() {
+		let response = ZipResponse::new("test.zip".into(), |zip| {
+			zip.start_file("small.txt", SimpleFileOptions::default())?;
+			zip.write_all(b"hello")?;
+			zip.start_file(
+				"big.bin",
+				SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+			)?;
+			zip.write_all(&big_entry())?;
+			Ok(())
+		})
+		.into_response();
+		assert_eq!(response.headers()[header::CONTENT_TYPE], "application/zip");
+		let zip_bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+		let mut zip = ZipArchive::new(Cursor::new(zip_bytes)).unwrap();
+		let mut small = String::new();
+		zip.by_name("small.txt").unwrap().read_to_string(&mut small).unwrap();
+		assert_eq!(small, "hello");
+		let mut big = Vec::new();
+		zip.by_name("big.bin").unwrap().read_to_end(&mut big).unwrap();
+		assert_eq!(big, big_entry());
+	}
+
+	#[tokio::test]
+	async fn zip_responses_are_cut_off_when_writing_fails() {
+		let response = ZipResponse::new("test.zip".into(), |zip| {
+			zip.start_file(
+				"big.bin",
+				SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+			)?;
+			zip.write_all(&big_entry())?;
+			anyhow::bail!("the disk caught fire")
+		})
+		.into_response();
+		let mut body = response.into_body().into_data_stream();
+		let mut received = Vec::new();
+		let mut failed = false;
+		while let Some(chunk) = body.next().await {
+			match chunk {
+				Ok(chunk) => received.extend_from_slice(&chunk),
+				Err(_) => {
+					failed = true;
+					break;
+				},
+			}
+		}
+		assert!(failed, "the body should end with an error");
+		// Some of the zip was sent before the error, but it shouldn't look like a whole zip
+		assert!(!received.is_empty());
+		assert!(ZipArchive::new(Cursor::new(received)).is_err());
+	}
+}
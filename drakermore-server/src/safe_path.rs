@@ -0,0 +1,126 @@
+use std::{
+	io::{Error as IoError, ErrorKind as IoErrorKind},
+	path::{Component, Path, PathBuf},
+};
+
+use tokio::fs;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SafePathError {
+	#[error("cannot find {0}")]
+	NotFound(String),
+	#[error("{0} is not allowed")]
+	Forbidden(String),
+	#[error("{0} isn't a jar file name")]
+	NotAJar(String),
+	#[error(transparent)]
+	Io(IoError),
+}
+
+/// Turns a URL-supplied path into a relative path, rejecting anything which could point outside of the folder it's
+/// relative to. This doesn't touch the filesystem, so it doesn't protect against symlinks.
+pub fn safe_relative_path(untrusted_path: &str) -> Result<PathBuf, SafePathError> {
+	let mut relative_path = PathBuf::new();
+	for component in Path::new(untrusted_path).components() {
+		match component {
+			Component::Normal(component) => relative_path.push(component),
+			Component::CurDir => {},
+			Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+				return Err(SafePathError::Forbidden(untrusted_path.into()));
+			},
+		}
+	}
+	if relative_path.as_os_str().is_empty() {
+		return Err(SafePathError::NotFound(untrusted_path.into()));
+	}
+	Ok(relative_path)
+}
+
+/// Resolves a URL-supplied path inside of `root`. The result is guaranteed to be an existing file inside of `root`, even
+/// after following symlinks.
+pub async fn resolve_safe_path(root: &Path, untrusted_path: &str) -> Result<PathBuf, SafePathError> {
+	let relative_path = safe_relative_path(untrusted_path)?;
+	let root = fs::canonicalize(root).await.map_err(SafePathError::Io)?;
+	let full_path = fs::canonicalize(root.join(relative_path))
+		.await
+		.map_err(|err| match err.kind() {
+			IoErrorKind::NotFound => SafePathError::NotFound(untrusted_path.into()),
+			_ => SafePathError::Io(err),
+		})?;
+	if !full_path.starts_with(&root) {
+		return Err(SafePathError::Forbidden(untrusted_path.into()));
+	}
+	if !fs::metadata(&full_path).await.map_err(SafePathError::Io)?.is_file() {
+		return Err(SafePathError::NotFound(untrusted_path.into()));
+	}
+	Ok(full_path)
+}
+
+/// Makes sure a jar file name from a URL or a mod repo is nothing but a file name, so it can only point to a jar inside
+/// of the realm folder it's joined onto
+pub fn check_jar_file_name(untrusted_name: &str) -> Result<(), SafePathError> {
+	let relative_path = safe_relative_path(untrusted_name)?;
+	if relative_path.components().count() != 1 || untrusted_name.starts_with('.') {
+		return Err(SafePathError::Forbidden(untrusted_name.into()));
+	}
+	if !untrusted_name.ends_with(".jar") {
+		return Err(SafePathError::NotAJar(untrusted_name.into()));
+	}
+	Ok(())
+}
+
+pub async fn remove_file_if_exists(path: &Path) -> anyhow::Result<()> {
+	match fs::remove_file(path).await {
+		Err(err) if err.kind() != IoErrorKind::NotFound => Err(err.into()),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn relative_paths_are_normalized() {
+		assert_eq!(
+			safe_relative_path("config/./foo.json").unwrap(),
+			PathBuf::from("config/foo.json")
+		);
+		assert_eq!(safe_relative_path("foo.jar").unwrap(), PathBuf::from("foo.jar"));
+	}
+
+	#[test]
+	fn escaping_relative_paths_are_forbidden() {
+		for untrusted_path in ["../secret", "config/../../secret", "/etc/passwd", "config/.."] {
+			assert!(
+				matches!(safe_relative_path(untrusted_path), Err(SafePathError::Forbidden(_))),
+				"{untrusted_path} should be forbidden"
+			);
+		}
+	}
+
+	#[test]
+	fn jar_file_names_are_plain_jar_file_names() {
+		assert!(check_jar_file_name("sodium-0.5.jar").is_ok());
+		for untrusted_name in ["mods/sodium.jar", "../sodium.jar", ".sodium.jar", "/sodium.jar"] {
+			assert!(
+				matches!(check_jar_file_name(untrusted_name), Err(SafePathError::Forbidden(_))),
+				"{untrusted_name} should be forbidden"
+			);
+		}
+		assert!(matches!(
+			check_jar_file_name("sodium.zip"),
+			Err(SafePathError::NotAJar(_))
+		));
+	}
+
+	#[test]
+	fn empty_relative_paths_are_not_found() {
+		for untrusted_path in ["", ".", "./"] {
+			assert!(
+				matches!(safe_relative_path(untrusted_path), Err(SafePathError::NotFound(_))),
+				"{untrusted_path} should not be found"
+			);
+		}
+	}
+}
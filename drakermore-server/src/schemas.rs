@@ -1,12 +1,15 @@
 use std::{
 	borrow::Cow,
+	collections::BTreeMap,
 	fmt::Display,
 	io::{Error as IoError, ErrorKind as IoErrorKind},
+	path::{Path, PathBuf},
 	str::FromStr,
 };
 
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use tokio::fs;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct MmcPackComponent {
@@ -45,21 +48,223 @@ pub enum ModRepoChannel {
 	#[serde(rename = "alpha")]
 	Alpha,
 }
+impl ModRepoChannel {
+	/// Whether a file released on `channel` is stable enough, e.g. asking for betas still accepts full releases
+	pub fn accepts(self, channel: ModRepoChannel) -> bool {
+		channel.stability() >= self.stability()
+	}
+	fn stability(self) -> u8 {
+		match self {
+			ModRepoChannel::Alpha => 0,
+			ModRepoChannel::Beta => 1,
+			ModRepoChannel::Release => 2,
+		}
+	}
+}
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ModListItem {
 	pub id: String,
 	pub repo: ModRepo,
 	pub channel: ModRepoChannel,
 }
+/// Where a downloaded jar came from, read from the `{jar}.source.toml` file next to it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ModSource {
+	pub repo: ModRepo,
+	/// Modrinth project id or CurseForge project id
+	pub project_id: String,
+	/// Modrinth version id or CurseForge file id
+	pub version_id: String,
+}
+impl ModSource {
+	/// The project and file ids if the jar came from CurseForge, which always uses numbers for them
+	pub fn curseforge_ids(&self) -> anyhow::Result<Option<(u32, u32)>> {
+		if self.repo != ModRepo::Curseforge {
+			return Ok(None);
+		}
+		let parse_id = |id_kind: &str, id: &str| {
+			id.parse()
+				.map_err(|err| anyhow::anyhow!("CurseForge {id_kind} id \"{id}\" isn't a number: {err}"))
+		};
+		Ok(Some((
+			parse_id("project", &self.project_id)?,
+			parse_id("file", &self.version_id)?,
+		)))
+	}
+	pub fn packwiz_update(&self) -> anyhow::Result<PackwizModUpdate<'_>> {
+		Ok(match self.curseforge_ids()? {
+			Some((project_id, file_id)) => PackwizModUpdate::Curseforge { project_id, file_id },
+			None => PackwizModUpdate::Modrinth {
+				mod_id: &self.project_id,
+				version: &self.version_id,
+			},
+		})
+	}
+}
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DrakermoreModConfig {
 	pub name: String,
 	pub minecraft_version: String,
 	pub pack_author: String,
 	pub pack_version: String,
-	pub fabric_loader_version: String,
+	#[serde(default)]
+	pub loader: ModLoader,
+	#[serde(alias = "fabric_loader_version")]
+	pub loader_version: String,
+	/// Defaults to the components required by `loader` if not specified
+	#[serde(default)]
 	pub mmc_pack_components: Vec<MmcPackComponent>,
 	pub minecraft_servers: Vec<MinecraftClientServerListInfo>,
+	#[serde(default)]
+	pub mod_list: Vec<ModListItem>,
+	/// Sides for copy dir files which aren't in a top-level `client/`, `server/` or `both/` folder, keyed by their path
+	/// relative to the copy dir. A folder's side applies to everything in it, and the most specific path wins.
+	#[serde(default)]
+	pub copy_file_sides: BTreeMap<PathBuf, PackwizModSide>,
+	/// Mods players can choose whether to install, keyed by jar file name or fabric mod id. A `{jar}.option.toml` file
+	/// next to the jar takes priority.
+	#[serde(default)]
+	pub mod_options: BTreeMap<String, PackwizModOption>,
+	/// Leave jars whose fabric.mod.json says they need a different Minecraft or loader version out of the pack,
+	/// rather than just warning about them
+	#[serde(default)]
+	pub exclude_incompatible_jars: bool,
+	/// Don't publish a new packwiz index while the same mod is in the pack more than once, rather than just warning
+	/// about it. The previous index keeps being served, or the server doesn't start if there isn't one yet.
+	#[serde(default)]
+	pub reject_duplicate_jars: bool,
+	/// Jars downloaded by hand for CurseForge files whose authors don't allow them to be downloaded anywhere else,
+	/// keyed by mod list id or project id. Relative paths are relative to the config file, and the jars still have to
+	/// match the hashes CurseForge gives.
+	#[serde(default)]
+	pub manual_downloads: BTreeMap<String, PathBuf>,
+}
+impl DrakermoreModConfig {
+	/// Reads the config file, fills in the default MMC components, and makes sure they match the specified loader
+	pub async fn read_from_file(file_path: &Path) -> anyhow::Result<Self> {
+		let mut config: Self = toml::from_str(&fs::read_to_string(file_path).await?)?;
+		if config.mmc_pack_components.is_empty() {
+			config.mmc_pack_components = config
+				.loader
+				.mmc_pack_components(&config.minecraft_version, &config.loader_version);
+		} else {
+			config.validate_mmc_pack_components()?;
+		}
+		if let Some(config_dir) = file_path.parent() {
+			for jar_path in config.manual_downloads.values_mut() {
+				*jar_path = config_dir.join(&*jar_path);
+			}
+		}
+		Ok(config)
+	}
+	fn validate_mmc_pack_components(&self) -> anyhow::Result<()> {
+		let expect_component = |uid: &str, version: &str| -> anyhow::Result<()> {
+			match self.mmc_pack_components.iter().find(|component| component.uid == uid) {
+				Some(component) if component.version == version => Ok(()),
+				Some(component) => anyhow::bail!(
+					"mmc_pack_components has {uid} version {} but the config specifies version {version}",
+					component.version
+				),
+				None => anyhow::bail!(
+					"mmc_pack_components is missing {uid}, which is required by {}",
+					self.loader
+				),
+			}
+		};
+		expect_component(MINECRAFT_MMC_UID, &self.minecraft_version)?;
+		expect_component(self.loader.mmc_uid(), &self.loader_version)?;
+		for other_loader in ModLoader::all().filter(|other_loader| *other_loader != self.loader) {
+			if self
+				.mmc_pack_components
+				.iter()
+				.any(|component| component.uid == other_loader.mmc_uid())
+			{
+				anyhow::bail!(
+					"mmc_pack_components contains {} for {other_loader}, but the config's loader is {}",
+					other_loader.mmc_uid(),
+					self.loader
+				);
+			}
+		}
+		Ok(())
+	}
+}
+
+const MINECRAFT_MMC_UID: &str = "net.minecraft";
+const INTERMEDIARY_MMC_UID: &str = "net.fabricmc.intermediary";
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ModLoader {
+	#[default]
+	#[serde(rename = "fabric")]
+	Fabric,
+	#[serde(rename = "quilt")]
+	Quilt,
+	#[serde(rename = "forge")]
+	Forge,
+	#[serde(rename = "neoforge")]
+	NeoForge,
+}
+impl ModLoader {
+	pub fn all() -> impl DoubleEndedIterator<Item = ModLoader> {
+		static LOADERS: [ModLoader; 4] = [
+			ModLoader::Fabric,
+			ModLoader::Quilt,
+			ModLoader::Forge,
+			ModLoader::NeoForge,
+		];
+		LOADERS.into_iter()
+	}
+	/// The uid of the loader's component in an mmc-pack.json
+	pub fn mmc_uid(self) -> &'static str {
+		match self {
+			ModLoader::Fabric => "net.fabricmc.fabric-loader",
+			ModLoader::Quilt => "org.quiltmc.quilt-loader",
+			ModLoader::Forge => "net.minecraftforge",
+			ModLoader::NeoForge => "net.neoforged",
+		}
+	}
+	/// The key of the loader's version in a `modrinth.index.json`'s dependencies
+	pub fn mrpack_dependency(self) -> &'static str {
+		match self {
+			ModLoader::Fabric => "fabric-loader",
+			ModLoader::Quilt => "quilt-loader",
+			ModLoader::Forge => "forge",
+			ModLoader::NeoForge => "neoforge",
+		}
+	}
+	/// The minimal set of components MultiMC/Prism needs to launch the loader, any missing libraries (e.g. lwjgl) are
+	/// resolved by the launcher itself
+	pub fn mmc_pack_components(self, minecraft_version: &str, loader_version: &str) -> Vec<MmcPackComponent> {
+		let mut components = vec![MmcPackComponent {
+			uid: MINECRAFT_MMC_UID.into(),
+			version: minecraft_version.into(),
+			dependency_only: false,
+		}];
+		if matches!(self, ModLoader::Fabric | ModLoader::Quilt) {
+			components.push(MmcPackComponent {
+				uid: INTERMEDIARY_MMC_UID.into(),
+				version: minecraft_version.into(),
+				dependency_only: true,
+			});
+		}
+		components.push(MmcPackComponent {
+			uid: self.mmc_uid().into(),
+			version: loader_version.into(),
+			dependency_only: false,
+		});
+		components
+	}
+}
+impl Display for ModLoader {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ModLoader::Fabric => f.write_str("fabric"),
+			ModLoader::Quilt => f.write_str("quilt"),
+			ModLoader::Forge => f.write_str("forge"),
+			ModLoader::NeoForge => f.write_str("neoforge"),
+		}
+	}
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -102,10 +307,32 @@ pub struct PackwizMetadata<'a> {
 	pub index: PackwizMetadataIndex<'a>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Default, Serialize, Clone)]
 pub struct PackwizMetadataVersions<'a> {
 	pub minecraft: Cow<'a, str>,
-	pub fabric: Cow<'a, str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub fabric: Option<Cow<'a, str>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub quilt: Option<Cow<'a, str>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub forge: Option<Cow<'a, str>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub neoforge: Option<Cow<'a, str>>,
+}
+impl<'a> PackwizMetadataVersions<'a> {
+	pub fn new(minecraft: Cow<'a, str>, loader: ModLoader, loader_version: Cow<'a, str>) -> Self {
+		let mut versions = Self {
+			minecraft,
+			..Default::default()
+		};
+		*match loader {
+			ModLoader::Fabric => &mut versions.fabric,
+			ModLoader::Quilt => &mut versions.quilt,
+			ModLoader::Forge => &mut versions.forge,
+			ModLoader::NeoForge => &mut versions.neoforge,
+		} = Some(loader_version);
+		versions
+	}
 }
 #[derive(Debug, Serialize, Clone)]
 pub struct PackwizMetadataIndex<'a> {
@@ -121,8 +348,40 @@ pub struct PackwizMod<'a> {
 	pub filename: Cow<'a, str>,
 	pub side: PackwizModSide,
 	pub download: PackwizModDownload<'a>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub option: Option<&'a PackwizModOption>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub update: Option<PackwizModUpdate<'a>>,
+}
+/// Lets packwiz know where to look for new versions of the mod, serialized as `[update.modrinth]` or
+/// `[update.curseforge]`
+#[derive(Debug, Serialize, Clone)]
+pub enum PackwizModUpdate<'a> {
+	#[serde(rename = "modrinth")]
+	Modrinth {
+		#[serde(rename = "mod-id")]
+		mod_id: &'a str,
+		version: &'a str,
+	},
+	#[serde(rename = "curseforge")]
+	Curseforge {
+		#[serde(rename = "project-id")]
+		project_id: u32,
+		#[serde(rename = "file-id")]
+		file_id: u32,
+	},
 }
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+/// Lets packwiz-installer ask players whether they want the mod
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PackwizModOption {
+	pub optional: bool,
+	/// Whether the mod is selected when the player is first asked
+	#[serde(default)]
+	pub default: bool,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+}
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum PackwizModSide {
 	#[serde(rename = "server")]
 	Server,
@@ -134,7 +393,23 @@ pub enum PackwizModSide {
 impl PackwizModSide {
 	pub fn all() -> impl DoubleEndedIterator<Item = PackwizModSide> {
 		static DIRECTIONS: [PackwizModSide; 3] = [PackwizModSide::Server, PackwizModSide::Client, PackwizModSide::Both];
-		DIRECTIONS.clone().into_iter()
+		DIRECTIONS.into_iter()
+	}
+	/// The side which covers both sides
+	pub fn union(self, other: PackwizModSide) -> PackwizModSide {
+		if self == other {
+			self
+		} else {
+			PackwizModSide::Both
+		}
+	}
+	/// The side which both sides cover, if there is one
+	pub fn intersection(self, other: PackwizModSide) -> Option<PackwizModSide> {
+		match (self, other) {
+			(PackwizModSide::Both, side) | (side, PackwizModSide::Both) => Some(side),
+			(a, b) if a == b => Some(a),
+			_ => None,
+		}
 	}
 }
 
@@ -170,12 +445,200 @@ pub struct PackwizModDownload<'a> {
 	pub hash: Cow<'a, str>,
 }
 
-#[derive(Debug, Serialize, Clone)]
-pub struct MinecraftClientServerList {
-	pub servers: Vec<MinecraftClientServerListInfo>,
-}
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MinecraftClientServerListInfo {
 	pub name: String,
 	pub ip: String,
 }
+
+#[derive(Debug, Default, Serialize_repr, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MrpackFormatVersion {
+	#[default]
+	V1 = 1,
+}
+#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum MrpackGame {
+	#[default]
+	#[serde(rename = "minecraft")]
+	Minecraft,
+}
+/// The `modrinth.index.json` at the root of a `.mrpack`
+#[derive(Debug, Serialize, Clone)]
+pub struct MrpackIndex<'a> {
+	#[serde(rename = "formatVersion")]
+	pub format_version: MrpackFormatVersion,
+	pub game: MrpackGame,
+	#[serde(rename = "versionId")]
+	pub version_id: &'a str,
+	pub name: &'a str,
+	pub files: Vec<MrpackFile<'a>>,
+	/// Minecraft and loader versions, keyed by "minecraft" or `ModLoader::mrpack_dependency`
+	pub dependencies: BTreeMap<&'a str, &'a str>,
+}
+#[derive(Debug, Serialize, Clone)]
+pub struct MrpackFile<'a> {
+	pub path: Cow<'a, str>,
+	pub hashes: MrpackFileHashes<'a>,
+	pub env: MrpackFileEnv,
+	pub downloads: Vec<Cow<'a, str>>,
+	#[serde(rename = "fileSize")]
+	pub file_size: u64,
+}
+#[derive(Debug, Serialize, Clone)]
+pub struct MrpackFileHashes<'a> {
+	pub sha1: Cow<'a, str>,
+	pub sha512: Cow<'a, str>,
+}
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct MrpackFileEnv {
+	pub client: MrpackEnvSupport,
+	pub server: MrpackEnvSupport,
+}
+impl MrpackFileEnv {
+	pub fn new(side: PackwizModSide, optional: bool) -> Self {
+		let client = if optional {
+			MrpackEnvSupport::Optional
+		} else {
+			MrpackEnvSupport::Required
+		};
+		match side {
+			PackwizModSide::Client => Self {
+				client,
+				server: MrpackEnvSupport::Unsupported,
+			},
+			PackwizModSide::Server => Self {
+				client: MrpackEnvSupport::Unsupported,
+				server: MrpackEnvSupport::Required,
+			},
+			PackwizModSide::Both => Self {
+				client,
+				server: MrpackEnvSupport::Required,
+			},
+		}
+	}
+}
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum MrpackEnvSupport {
+	#[serde(rename = "required")]
+	Required,
+	#[serde(rename = "optional")]
+	Optional,
+	#[serde(rename = "unsupported")]
+	Unsupported,
+}
+
+#[derive(Debug, Default, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CurseforgeManifestType {
+	#[default]
+	#[serde(rename = "minecraftModpack")]
+	MinecraftModpack,
+}
+#[derive(Debug, Default, Serialize_repr, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CurseforgeManifestVersion {
+	#[default]
+	V1 = 1,
+}
+/// The `manifest.json` at the root of a CurseForge modpack zip
+#[derive(Debug, Serialize, Clone)]
+pub struct CurseforgeManifest<'a> {
+	pub minecraft: CurseforgeManifestMinecraft,
+	#[serde(rename = "manifestType")]
+	pub manifest_type: CurseforgeManifestType,
+	#[serde(rename = "manifestVersion")]
+	pub manifest_version: CurseforgeManifestVersion,
+	pub name: &'a str,
+	pub version: &'a str,
+	pub author: &'a str,
+	pub files: Vec<CurseforgeManifestFile>,
+	/// The folder in the zip which gets copied over the instance
+	pub overrides: &'a str,
+}
+#[derive(Debug, Serialize, Clone)]
+pub struct CurseforgeManifestMinecraft {
+	pub version: String,
+	#[serde(rename = "modLoaders")]
+	pub mod_loaders: Vec<CurseforgeManifestModLoader>,
+}
+#[derive(Debug, Serialize, Clone)]
+pub struct CurseforgeManifestModLoader {
+	/// e.g. "fabric-0.16.5"
+	pub id: String,
+	pub primary: bool,
+}
+#[derive(Debug, Serialize, Clone)]
+pub struct CurseforgeManifestFile {
+	#[serde(rename = "projectID")]
+	pub project_id: u32,
+	#[serde(rename = "fileID")]
+	pub file_id: u32,
+	pub required: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_utils::test_config_toml;
+
+	async fn read_config(mmc_pack_components: &str) -> anyhow::Result<DrakermoreModConfig> {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let config_path = temp_dir.path().join("mod-list.toml");
+		std::fs::write(
+			&config_path,
+			test_config_toml(&format!(
+				r#"
+loader = "quilt"
+loader_version = "0.26.0"
+mmc_pack_components = {mmc_pack_components}
+"#
+			)),
+		)
+		.unwrap();
+		DrakermoreModConfig::read_from_file(&config_path).await
+	}
+
+	#[tokio::test]
+	async fn mmc_pack_components_have_to_match_the_loader() {
+		let config = read_config("[]").await.unwrap();
+		let uids: Vec<_> = config
+			.mmc_pack_components
+			.iter()
+			.map(|component| component.uid.as_str())
+			.collect();
+		assert_eq!(
+			uids,
+			[MINECRAFT_MMC_UID, INTERMEDIARY_MMC_UID, "org.quiltmc.quilt-loader"]
+		);
+
+		let matching = r#"[
+	{ uid = "net.minecraft", version = "1.20.1" },
+	{ uid = "org.quiltmc.quilt-loader", version = "0.26.0" },
+]"#;
+		assert!(read_config(matching).await.is_ok());
+		for (mmc_pack_components, expected_error) in [
+			(
+				r#"[{ uid = "net.minecraft", version = "1.20.1" }]"#,
+				"missing org.quiltmc.quilt-loader",
+			),
+			(
+				r#"[
+	{ uid = "net.minecraft", version = "1.20.4" },
+	{ uid = "org.quiltmc.quilt-loader", version = "0.26.0" },
+]"#,
+				"net.minecraft version 1.20.4",
+			),
+			(
+				r#"[
+	{ uid = "net.minecraft", version = "1.20.1" },
+	{ uid = "org.quiltmc.quilt-loader", version = "0.26.0" },
+	{ uid = "net.fabricmc.fabric-loader", version = "0.16.5" },
+]"#,
+				"net.fabricmc.fabric-loader for fabric",
+			),
+		] {
+			let err = read_config(mmc_pack_components).await.unwrap_err().to_string();
+			assert!(err.contains(expected_error), "{err}");
+		}
+	}
+}
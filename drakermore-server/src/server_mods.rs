@@ -0,0 +1,278 @@
+use std::{
+	collections::{BTreeMap, HashSet},
+	io::ErrorKind as IoErrorKind,
+	os::unix::fs::MetadataExt,
+	path::{Path, PathBuf},
+	str::FromStr,
+	sync::atomic::{AtomicU64, Ordering},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::fs;
+
+use crate::{
+	safe_path::{resolve_safe_path, SafePathError},
+	schemas::PackwizModSide,
+};
+
+/// The realms whose jars the Minecraft server loads, earlier realms win when both have a jar with the same name
+const SERVER_REALMS: [PackwizModSide; 2] = [PackwizModSide::Server, PackwizModSide::Both];
+/// Counts the mods folder generations this process has made
+static GENERATION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// How the server's mods folder refers to the downloaded jars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerModsLink {
+	Symlink,
+	/// Needs the mods folder to be on the same filesystem as the download dir
+	Hardlink,
+}
+impl FromStr for ServerModsLink {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"symlink" => Ok(ServerModsLink::Symlink),
+			"hardlink" => Ok(ServerModsLink::Hardlink),
+			_ => Err(format!("\"{s}\" should be \"symlink\" or \"hardlink\"")),
+		}
+	}
+}
+
+/// A way in which the live mods folder differs from the downloaded server mods
+#[derive(Debug, thiserror::Error, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ServerModsDrift {
+	#[error("{0} isn't one of the downloaded server mods")]
+	Unmanaged(PathBuf),
+	#[error("{0} isn't the same file as {1}")]
+	Changed(PathBuf, PathBuf),
+	#[error("{0} points to a jar which doesn't exist anymore")]
+	Dangling(PathBuf),
+	#[error("{0} isn't in the mods folder")]
+	Unlinked(PathBuf),
+}
+impl ServerModsDrift {
+	/// Whether someone changed the mods folder by hand, rather than the downloaded mods having changed since it was
+	/// last linked
+	pub fn is_hand_edit(&self) -> bool {
+		matches!(self, ServerModsDrift::Unmanaged(_) | ServerModsDrift::Changed(..))
+	}
+}
+
+#[derive(Debug)]
+pub struct ServerModsSummary {
+	pub linked: usize,
+	/// How the mods folder differed from the download dir before it got replaced
+	pub drift: Vec<ServerModsDrift>,
+	/// Where the mods folder was moved to, if it was a real folder rather than one we linked before
+	pub replaced_dir: Option<PathBuf>,
+}
+
+/// The jars the server should load keyed by file name, mapped to their canonical paths
+pub async fn server_jars(download_dir: &Path) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+	let mut jars = BTreeMap::new();
+	for realm in SERVER_REALMS {
+		let mut dir_reader = fs::read_dir(download_dir.join(realm.to_string())).await?;
+		while let Some(dir_entry) = dir_reader.next_entry().await? {
+			let jar_file_name = dir_entry.file_name().to_string_lossy().into_owned();
+			if !jar_file_name.ends_with(".jar") || jars.contains_key(&jar_file_name) {
+				continue;
+			}
+			match resolve_safe_path(download_dir, &format!("{realm}/{jar_file_name}")).await {
+				Ok(jar_path) => {
+					jars.insert(jar_file_name, jar_path);
+				},
+				Err(SafePathError::Forbidden(path)) => {
+					tracing::warn!("Not linking {path} since it points outside of the download dir");
+				},
+				Err(SafePathError::NotFound(_)) => {},
+				Err(err) => return Err(err.into()),
+			}
+		}
+	}
+	Ok(jars)
+}
+
+/// Compares the live mods folder against the downloaded server mods
+pub async fn server_mods_drift(download_dir: &Path, mods_dir: &Path) -> anyhow::Result<Vec<ServerModsDrift>> {
+	find_drift(&server_jars(download_dir).await?, mods_dir).await
+}
+
+async fn find_drift(jars: &BTreeMap<String, PathBuf>, mods_dir: &Path) -> anyhow::Result<Vec<ServerModsDrift>> {
+	let mut drift = Vec::new();
+	let mut dir_reader = match fs::read_dir(mods_dir).await {
+		Ok(dir_reader) => dir_reader,
+		Err(err) if err.kind() == IoErrorKind::NotFound => {
+			return Ok(jars.values().cloned().map(ServerModsDrift::Unlinked).collect());
+		},
+		Err(err) => return Err(err.into()),
+	};
+	let mut linked_jars = HashSet::new();
+	while let Some(dir_entry) = dir_reader.next_entry().await? {
+		let path = dir_entry.path();
+		let file_name = dir_entry.file_name().to_string_lossy().into_owned();
+		// Following the link and comparing inodes works the same for symlinks and hardlinks
+		let metadata = match fs::metadata(&path).await {
+			Ok(metadata) => metadata,
+			Err(err) if err.kind() == IoErrorKind::NotFound => {
+				linked_jars.insert(file_name);
+				drift.push(ServerModsDrift::Dangling(path));
+				continue;
+			},
+			Err(err) => return Err(err.into()),
+		};
+		let Some(jar_path) = jars.get(&file_name) else {
+			drift.push(ServerModsDrift::Unmanaged(path));
+			continue;
+		};
+		linked_jars.insert(file_name);
+		let jar_metadata = fs::metadata(jar_path).await?;
+		if (metadata.dev(), metadata.ino()) != (jar_metadata.dev(), jar_metadata.ino()) {
+			drift.push(ServerModsDrift::Changed(path, jar_path.clone()));
+		}
+	}
+	for (jar_file_name, jar_path) in jars.iter() {
+		if !linked_jars.contains(jar_file_name) {
+			drift.push(ServerModsDrift::Unlinked(jar_path.clone()));
+		}
+	}
+	drift.sort();
+	Ok(drift)
+}
+
+/// Links every server jar into a new folder next to the mods folder, then swaps it in by pointing the mods folder at
+/// it, so the server never sees a half-linked mods folder. A real folder in the way gets moved aside the first time.
+pub async fn link_server_mods(
+	download_dir: &Path,
+	mods_dir: &Path,
+	link: ServerModsLink,
+) -> anyhow::Result<ServerModsSummary> {
+	let jars = server_jars(download_dir).await?;
+	let drift = find_drift(&jars, mods_dir).await?;
+	let (Some(parent_dir), Some(mods_dir_name)) = (mods_dir.parent(), mods_dir.file_name()) else {
+		anyhow::bail!("{} can't be used as a mods folder", mods_dir.display());
+	};
+	let generation_prefix = format!(".{}.drakermore-", mods_dir_name.to_string_lossy());
+	// The timestamp keeps the names in order, the process id and counter keep runs in the same millisecond apart
+	let generation_name = format!(
+		"{generation_prefix}{}-{}-{}",
+		SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis(),
+		std::process::id(),
+		GENERATION_COUNTER.fetch_add(1, Ordering::Relaxed)
+	);
+	let generation_dir = parent_dir.join(&generation_name);
+	fs::create_dir(&generation_dir).await?;
+	if let Err(err) = link_jars(&jars, &generation_dir, link).await {
+		fs::remove_dir_all(&generation_dir).await?;
+		return Err(err);
+	}
+
+	let mut previous_generation = None;
+	let mut replaced_dir = None;
+	match fs::symlink_metadata(mods_dir).await {
+		Ok(metadata) if metadata.is_symlink() => {
+			previous_generation = Some(fs::read_link(mods_dir).await?);
+		},
+		Ok(_) => {
+			let moved_dir = parent_dir.join(format!("{}.replaced", generation_prefix.trim_end_matches('-')));
+			if fs::try_exists(&moved_dir).await? {
+				fs::remove_dir_all(&moved_dir).await?;
+			}
+			fs::rename(mods_dir, &moved_dir).await?;
+			replaced_dir = Some(moved_dir);
+		},
+		Err(err) if err.kind() == IoErrorKind::NotFound => {},
+		Err(err) => return Err(err.into()),
+	}
+	// Renaming a symlink over another one is atomic, unlike anything involving a real folder
+	let swap_path = parent_dir.join(format!("{generation_prefix}swap"));
+	if fs::symlink_metadata(&swap_path).await.is_ok() {
+		fs::remove_file(&swap_path).await?;
+	}
+	fs::symlink(&generation_name, &swap_path).await?;
+	fs::rename(&swap_path, mods_dir).await?;
+
+	// Folders the mods folder was pointed at by someone else are left alone
+	if let Some(previous_generation) = previous_generation.filter(|previous_generation| {
+		previous_generation
+			.to_str()
+			.is_some_and(|previous_generation| previous_generation.starts_with(&generation_prefix))
+	}) {
+		if let Err(err) = fs::remove_dir_all(parent_dir.join(&previous_generation)).await {
+			tracing::warn!("Couldn't remove {}: {err}", previous_generation.display());
+		}
+	}
+	Ok(ServerModsSummary {
+		linked: jars.len(),
+		drift,
+		replaced_dir,
+	})
+}
+
+async fn link_jars(jars: &BTreeMap<String, PathBuf>, dir: &Path, link: ServerModsLink) -> anyhow::Result<()> {
+	for (jar_file_name, jar_path) in jars.iter() {
+		let link_path = dir.join(jar_file_name);
+		match link {
+			ServerModsLink::Symlink => fs::symlink(jar_path, &link_path).await?,
+			ServerModsLink::Hardlink => fs::hard_link(jar_path, &link_path).await?,
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn mods_folder_is_swapped_for_the_server_jars() {
+		let temp_dir = tempfile::tempdir().unwrap();
+		let download_dir = temp_dir.path().join("download");
+		for realm in PackwizModSide::all() {
+			fs::create_dir_all(download_dir.join(realm.to_string())).unwrap();
+		}
+		fs::write(download_dir.join("server/lazydfu.jar"), "server").unwrap();
+		fs::write(download_dir.join("both/lithium.jar"), "both").unwrap();
+		fs::write(download_dir.join("both/lithium.jar.name.txt"), "Lithium\n").unwrap();
+		fs::write(download_dir.join("client/sodium.jar"), "client").unwrap();
+		let download_dir = download_dir.canonicalize().unwrap();
+		let mods_dir = temp_dir.path().join("mods");
+		fs::create_dir(&mods_dir).unwrap();
+		fs::write(mods_dir.join("hand-added.jar"), "by hand").unwrap();
+
+		let summary = link_server_mods(&download_dir, &mods_dir, ServerModsLink::Symlink)
+			.await
+			.unwrap();
+		assert_eq!(summary.linked, 2);
+		assert_eq!(
+			summary.drift,
+			[
+				ServerModsDrift::Unmanaged(mods_dir.join("hand-added.jar")),
+				ServerModsDrift::Unlinked(download_dir.join("both/lithium.jar")),
+				ServerModsDrift::Unlinked(download_dir.join("server/lazydfu.jar")),
+			]
+		);
+		let replaced_dir = summary.replaced_dir.unwrap();
+		assert!(replaced_dir.join("hand-added.jar").exists());
+		assert!(fs::symlink_metadata(&mods_dir).unwrap().is_symlink());
+		assert_eq!(fs::read_to_string(mods_dir.join("lithium.jar")).unwrap(), "both");
+		assert!(!mods_dir.join("sodium.jar").exists());
+		assert!(server_mods_drift(&download_dir, &mods_dir).await.unwrap().is_empty());
+
+		let first_generation = fs::read_link(&mods_dir).unwrap();
+		fs::remove_file(download_dir.join("server/lazydfu.jar")).unwrap();
+		assert_eq!(
+			server_mods_drift(&download_dir, &mods_dir).await.unwrap(),
+			[ServerModsDrift::Dangling(mods_dir.join("lazydfu.jar"))]
+		);
+		let summary = link_server_mods(&download_dir, &mods_dir, ServerModsLink::Hardlink)
+			.await
+			.unwrap();
+		assert_eq!(summary.linked, 1);
+		assert!(summary.replaced_dir.is_none());
+		assert!(!temp_dir.path().join(first_generation).exists());
+		assert!(!fs::symlink_metadata(mods_dir.join("lithium.jar")).unwrap().is_symlink());
+		assert!(server_mods_drift(&download_dir, &mods_dir).await.unwrap().is_empty());
+	}
+}
@@ -0,0 +1,708 @@
+use std::{
+	collections::HashSet,
+	io::ErrorKind as IoErrorKind,
+	path::{Path, PathBuf},
+};
+
+use lazy_regex::regex_is_match;
+use sha1::Sha1;
+use sha2::{Digest, Sha512};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+	fabric_mod::{read_fabric_mod_or_warn, FabricModJson},
+	lockfile::{LockedMod, Lockfile},
+	resolvers::{http_client, DependencyRequest, ModResolver, ResolvedMod},
+	safe_path::{check_jar_file_name, remove_file_if_exists},
+	schemas::{DrakermoreModConfig, ModListItem, ModSource, PackwizModSide},
+};
+
+/// Downloads are kept here until every one of them has been verified. It's inside the download dir so files can be
+/// renamed into place, and the packwiz cache ignores it since it isn't a realm.
+const STAGING_DIR_NAME: &str = ".sync-staging";
+/// Mod ids which Fabric, Quilt or Minecraft itself provide
+const FABRIC_BUILTIN_MOD_IDS: [&str; 5] = ["minecraft", "java", "fabricloader", "fabric-loader", "quilt_loader"];
+/// The files we write next to each jar, hand-written ones like `.option.toml` are left alone
+const SYNC_SIDECAR_SUFFIXES: [&str; 2] = [".name.txt", ".source.toml"];
+
+/// A jar which was put in the download dir by a previous sync or by the scraper
+#[derive(Debug)]
+pub struct ManagedJar {
+	pub path: PathBuf,
+	pub source: ModSource,
+}
+
+/// A jar which is ready to be moved into its realm folder
+#[derive(Debug)]
+struct StagedJar {
+	/// None if the jar is already where it should be
+	staged_path: Option<PathBuf>,
+	/// Whether the jar came from the mod repo rather than from somewhere else in the download dir
+	downloaded: bool,
+	target_path: PathBuf,
+	/// hex-encoded sha512 hash of the jar, for the lockfile
+	sha512: String,
+	fabric_mod: Option<FabricModJson>,
+}
+
+#[derive(Debug, Default)]
+pub struct SyncSummary {
+	pub downloaded: usize,
+	pub moved: usize,
+	pub unchanged: usize,
+	pub removed: usize,
+	/// What every mod list item was resolved to, for writing to the lockfile
+	pub lockfile: Lockfile,
+}
+
+/// Makes the download dir match the mod list. Nothing in the realm folders is touched until every mod has been
+/// resolved and every download has been verified, so a failed sync leaves the previous set of mods as it was. Jars
+/// which weren't downloaded from a mod repo (i.e. ones without a `.source.toml` file) are left alone. The mods the mod
+/// list's mods need are synced too, and are removed again once nothing needs them.
+pub async fn sync_mods(
+	modpack: &DrakermoreModConfig,
+	download_dir: &Path,
+	mod_resolver: &ModResolver,
+) -> anyhow::Result<SyncSummary> {
+	let mut resolved_mods = mod_resolver.resolve_mod_list(modpack).await?;
+
+	for realm in PackwizModSide::all() {
+		fs::create_dir_all(download_dir.join(realm.to_string())).await?;
+	}
+	let managed_jars = find_managed_jars(download_dir).await?;
+	let staging_dir = download_dir.join(STAGING_DIR_NAME);
+	remove_dir_if_exists(&staging_dir).await?;
+	fs::create_dir(&staging_dir).await?;
+
+	let staged_jars = stage_with_fabric_dependencies(
+		&mut resolved_mods,
+		modpack,
+		mod_resolver,
+		&managed_jars,
+		download_dir,
+		&staging_dir,
+	)
+	.await;
+	let staged_jars = match staged_jars {
+		Ok(staged_jars) => staged_jars,
+		Err(err) => {
+			remove_dir_if_exists(&staging_dir).await?;
+			return Err(err);
+		},
+	};
+
+	let mut summary = SyncSummary::default();
+	let mut wanted_paths = HashSet::new();
+	for (resolved_mod, staged_jar) in resolved_mods.into_iter().zip(staged_jars) {
+		summary.lockfile.mods.push(LockedMod {
+			id: resolved_mod.id,
+			source: resolved_mod.source,
+			file_name: resolved_mod.file_name,
+			side: resolved_mod.side,
+			size: resolved_mod.size,
+			sha512: staged_jar.sha512,
+			required_by: resolved_mod.required_by,
+		});
+		wanted_paths.insert(staged_jar.target_path.clone());
+		let Some(staged_path) = staged_jar.staged_path else {
+			summary.unchanged += 1;
+			continue;
+		};
+		if staged_jar.downloaded {
+			summary.downloaded += 1;
+		} else {
+			summary.moved += 1;
+		}
+		for sidecar_suffix in SYNC_SIDECAR_SUFFIXES {
+			fs::rename(
+				sidecar_path(&staged_path, sidecar_suffix),
+				sidecar_path(&staged_jar.target_path, sidecar_suffix),
+			)
+			.await?;
+		}
+		// The jar goes last so the file watcher sees its metadata as soon as it sees the jar
+		fs::rename(&staged_path, &staged_jar.target_path).await?;
+		tracing::info!("Updated {}", staged_jar.target_path.display());
+	}
+	for managed_jar in managed_jars {
+		if wanted_paths.contains(&managed_jar.path) {
+			continue;
+		}
+		fs::remove_file(&managed_jar.path).await?;
+		for sidecar_suffix in SYNC_SIDECAR_SUFFIXES {
+			remove_file_if_exists(&sidecar_path(&managed_jar.path, sidecar_suffix)).await?;
+		}
+		tracing::info!("Removed {}", managed_jar.path.display());
+		summary.removed += 1;
+	}
+	remove_dir_if_exists(&staging_dir).await?;
+	Ok(summary)
+}
+
+/// Stages every resolved mod, then adds and stages whatever their `fabric.mod.json` files need which the mod repos
+/// didn't mention, until nothing more can be found
+async fn stage_with_fabric_dependencies(
+	resolved_mods: &mut Vec<ResolvedMod>,
+	modpack: &DrakermoreModConfig,
+	mod_resolver: &ModResolver,
+	managed_jars: &[ManagedJar],
+	download_dir: &Path,
+	staging_dir: &Path,
+) -> anyhow::Result<Vec<StagedJar>> {
+	let mut provided_ids = unmanaged_fabric_mod_ids(download_dir).await?;
+	let mut requested_dependencies = HashSet::new();
+	let mut staged_jars: Vec<StagedJar> = Vec::with_capacity(resolved_mods.len());
+	while staged_jars.len() < resolved_mods.len() {
+		let new_mods = &resolved_mods[staged_jars.len()..];
+		for resolved_mod in new_mods {
+			check_jar_file_name(&resolved_mod.file_name)?;
+		}
+		let new_staged_jars = stage_jars(
+			new_mods,
+			staged_jars.len(),
+			modpack,
+			managed_jars,
+			download_dir,
+			staging_dir,
+		)
+		.await?;
+		for fabric_mod in new_staged_jars
+			.iter()
+			.filter_map(|staged_jar| staged_jar.fabric_mod.as_ref())
+		{
+			provided_ids.extend(fabric_mod.provided_ids().into_iter().map(String::from));
+		}
+		staged_jars.extend(new_staged_jars);
+
+		let mut dependency_requests = Vec::new();
+		for (resolved_mod, staged_jar) in resolved_mods.iter().zip(staged_jars.iter()) {
+			let Some(fabric_mod) = &staged_jar.fabric_mod else {
+				continue;
+			};
+			for dependency_id in fabric_mod.depends.keys() {
+				let project_id = fabric_dependency_project(dependency_id);
+				if FABRIC_BUILTIN_MOD_IDS.contains(&dependency_id.as_str())
+					|| provided_ids.contains(dependency_id)
+					|| provided_ids.contains(project_id)
+					|| !requested_dependencies.insert((resolved_mod.id.clone(), project_id.to_string()))
+				{
+					continue;
+				}
+				dependency_requests.push(DependencyRequest {
+					mod_list_item: ModListItem {
+						id: project_id.into(),
+						repo: resolved_mod.source.repo,
+						channel: resolved_mod.channel,
+					},
+					required_by: resolved_mod.id.clone(),
+					guessed: true,
+				});
+			}
+		}
+		mod_resolver
+			.add_dependencies(resolved_mods, dependency_requests, modpack)
+			.await?;
+		restage_moved_jars(
+			resolved_mods,
+			&mut staged_jars,
+			modpack,
+			managed_jars,
+			download_dir,
+			staging_dir,
+		)
+		.await?;
+	}
+	Ok(staged_jars)
+}
+
+/// Dependencies which were already staged can end up on another side once more of the mods which need them are found
+async fn restage_moved_jars(
+	resolved_mods: &[ResolvedMod],
+	staged_jars: &mut [StagedJar],
+	modpack: &DrakermoreModConfig,
+	managed_jars: &[ManagedJar],
+	download_dir: &Path,
+	staging_dir: &Path,
+) -> anyhow::Result<()> {
+	for (mod_index, (resolved_mod, staged_jar)) in resolved_mods.iter().zip(staged_jars.iter_mut()).enumerate() {
+		let target_path = realm_jar_path(download_dir, resolved_mod);
+		if staged_jar.target_path == target_path {
+			continue;
+		}
+		match staged_jar.staged_path {
+			Some(_) => staged_jar.target_path = target_path,
+			// The jar was already in place, so it has to be staged to be moved
+			None => {
+				let mut restaged_jars = stage_jars(
+					std::slice::from_ref(resolved_mod),
+					mod_index,
+					modpack,
+					managed_jars,
+					download_dir,
+					staging_dir,
+				)
+				.await?;
+				*staged_jar = restaged_jars.remove(0);
+			},
+		}
+	}
+	Ok(())
+}
+
+fn realm_jar_path(download_dir: &Path, resolved_mod: &ResolvedMod) -> PathBuf {
+	download_dir
+		.join(resolved_mod.side.to_string())
+		.join(&resolved_mod.file_name)
+}
+
+/// Fabric API is split into modules which are nested in its jar, and mods often depend on those modules rather than on
+/// Fabric API itself. Everything else is assumed to use its mod id as its slug.
+fn fabric_dependency_project(dependency_id: &str) -> &str {
+	if dependency_id == "fabric" || regex_is_match!(r"^fabric-[a-z0-9-]+-v\d+$", dependency_id) {
+		"fabric-api"
+	} else {
+		dependency_id
+	}
+}
+
+/// Mods put in the download dir by hand can satisfy dependencies too
+async fn unmanaged_fabric_mod_ids(download_dir: &Path) -> anyhow::Result<HashSet<String>> {
+	let mut provided_ids = HashSet::new();
+	for realm in PackwizModSide::all() {
+		let mut dir_reader = fs::read_dir(download_dir.join(realm.to_string())).await?;
+		while let Some(dir_entry) = dir_reader.next_entry().await? {
+			let path = dir_entry.path();
+			if path.extension().is_none_or(|ext| ext != "jar")
+				|| fs::try_exists(sidecar_path(&path, ".source.toml")).await?
+			{
+				continue;
+			}
+			if let Some(fabric_mod) = read_fabric_mod_or_warn(&path, &fs::read(&path).await?) {
+				provided_ids.extend(fabric_mod.provided_ids().into_iter().map(String::from));
+			}
+		}
+	}
+	Ok(provided_ids)
+}
+
+/// Downloads (or copies, if we already have it somewhere else) every jar which isn't already in the right place. Jars
+/// whose authors don't allow them to be downloaded from anywhere but their repo have to be downloaded by hand.
+async fn stage_jars(
+	resolved_mods: &[ResolvedMod],
+	first_mod_index: usize,
+	modpack: &DrakermoreModConfig,
+	managed_jars: &[ManagedJar],
+	download_dir: &Path,
+	staging_dir: &Path,
+) -> anyhow::Result<Vec<StagedJar>> {
+	let http = http_client()?;
+	let mut staged_jars = Vec::with_capacity(resolved_mods.len());
+	for (mod_index, resolved_mod) in (first_mod_index..).zip(resolved_mods) {
+		let target_path = realm_jar_path(download_dir, resolved_mod);
+		// Each mod gets its own file name so two mods with the same jar name can't overwrite each other
+		let staged_path = staging_dir.join(format!("{mod_index}.jar"));
+		let existing_jar = managed_jars
+			.iter()
+			.find(|managed_jar| managed_jar.source == resolved_mod.source);
+		let existing_bytes = match existing_jar {
+			// A jar which doesn't match its hashes anymore gets downloaded again
+			Some(existing_jar) => Some(fs::read(&existing_jar.path).await?)
+				.filter(|existing_bytes| verify_jar(resolved_mod, existing_bytes).is_ok()),
+			None => None,
+		};
+		let downloaded = existing_bytes.is_none();
+		let unchanged = !downloaded && existing_jar.is_some_and(|existing_jar| existing_jar.path == target_path);
+		let mut already_staged = false;
+		let jar_bytes = match existing_bytes {
+			Some(existing_bytes) => existing_bytes,
+			None => match (resolved_mod.manual_download(modpack), &resolved_mod.url) {
+				(Some(jar_path), _) => {
+					tracing::info!("Copying {} from {}", resolved_mod.id, jar_path.display());
+					let jar_bytes = fs::read(jar_path)
+						.await
+						.map_err(|err| anyhow::anyhow!("couldn't read {}: {err}", jar_path.display()))?;
+					verify_jar(resolved_mod, &jar_bytes)?;
+					jar_bytes
+				},
+				(None, Some(url)) if resolved_mod.distribution_allowed => {
+					download_jar(&http, url, resolved_mod, &staged_path).await?;
+					already_staged = true;
+					fs::read(&staged_path).await?
+				},
+				(None, _) if !resolved_mod.distribution_allowed => anyhow::bail!(
+					"{}'s author doesn't allow {} to be downloaded from anywhere but {:?}, download it by hand and add \
+					 it to manual_downloads",
+					resolved_mod.id,
+					resolved_mod.file_name,
+					resolved_mod.source.repo
+				),
+				(None, _) => anyhow::bail!(
+					"{:?} didn't give us a URL for {}'s {}, download it by hand and add it to manual_downloads",
+					resolved_mod.source.repo,
+					resolved_mod.id,
+					resolved_mod.file_name
+				),
+			},
+		};
+		let sha512 = hex::encode(Sha512::digest(&jar_bytes));
+		let fabric_mod = read_fabric_mod_or_warn(&target_path, &jar_bytes);
+		if unchanged {
+			staged_jars.push(StagedJar {
+				staged_path: None,
+				downloaded,
+				target_path,
+				sha512,
+				fabric_mod,
+			});
+			continue;
+		}
+		if !already_staged {
+			fs::write(&staged_path, jar_bytes).await?;
+		}
+		fs::write(
+			sidecar_path(&staged_path, ".name.txt"),
+			format!("{}\n", resolved_mod.name),
+		)
+		.await?;
+		fs::write(
+			sidecar_path(&staged_path, ".source.toml"),
+			toml::to_string_pretty(&resolved_mod.source)?,
+		)
+		.await?;
+		staged_jars.push(StagedJar {
+			staged_path: Some(staged_path),
+			downloaded,
+			target_path,
+			sha512,
+			fabric_mod,
+		});
+	}
+	Ok(staged_jars)
+}
+
+/// Streams the jar into `staged_path`, giving up as soon as it's bigger than the mod repo said it would be so a bad
+/// URL can't fill up memory or the disk
+async fn download_jar(
+	http: &reqwest::Client,
+	url: &str,
+	resolved_mod: &ResolvedMod,
+	staged_path: &Path,
+) -> anyhow::Result<()> {
+	tracing::info!("Downloading {url}");
+	let mut response = http.get(url).send().await?;
+	if !response.status().is_success() {
+		anyhow::bail!("{url} responded with {}", response.status());
+	}
+	let mut staged_file = fs::File::create(staged_path).await?;
+	let mut size = 0;
+	let mut sha1 = Sha1::new();
+	let mut sha512 = Sha512::new();
+	while let Some(chunk) = response.chunk().await? {
+		size += chunk.len() as u64;
+		if size > resolved_mod.size {
+			anyhow::bail!(
+				"{} should be {} bytes but {url} sent more than that",
+				resolved_mod.file_name,
+				resolved_mod.size
+			);
+		}
+		sha1.update(&chunk);
+		sha512.update(&chunk);
+		staged_file.write_all(&chunk).await?;
+	}
+	staged_file.flush().await?;
+	check_jar_hashes(
+		resolved_mod,
+		size,
+		&hex::encode(sha1.finalize()),
+		&hex::encode(sha512.finalize()),
+	)
+}
+
+/// Makes sure the file is exactly what the mod repo said it would be
+fn verify_jar(resolved_mod: &ResolvedMod, jar_bytes: &[u8]) -> anyhow::Result<()> {
+	check_jar_hashes(
+		resolved_mod,
+		jar_bytes.len() as u64,
+		&hex::encode(Sha1::digest(jar_bytes)),
+		&hex::encode(Sha512::digest(jar_bytes)),
+	)
+}
+fn check_jar_hashes(resolved_mod: &ResolvedMod, size: u64, sha1: &str, sha512: &str) -> anyhow::Result<()> {
+	if size != resolved_mod.size {
+		anyhow::bail!(
+			"{} should be {} bytes but it's {size} bytes",
+			resolved_mod.file_name,
+			resolved_mod.size
+		);
+	}
+	if sha1 != resolved_mod.sha1.to_lowercase() {
+		anyhow::bail!("{} doesn't match its sha1 hash", resolved_mod.file_name);
+	}
+	if resolved_mod
+		.sha512
+		.as_ref()
+		.is_some_and(|expected_sha512| sha512 != expected_sha512.to_lowercase())
+	{
+		anyhow::bail!("{} doesn't match its sha512 hash", resolved_mod.file_name);
+	}
+	Ok(())
+}
+
+pub async fn find_managed_jars(download_dir: &Path) -> anyhow::Result<Vec<ManagedJar>> {
+	let mut managed_jars = Vec::new();
+	for realm in PackwizModSide::all() {
+		let mut dir_reader = fs::read_dir(download_dir.join(realm.to_string())).await?;
+		while let Some(dir_entry) = dir_reader.next_entry().await? {
+			let path = dir_entry.path();
+			if path.extension().is_none_or(|ext| ext != "jar") {
+				continue;
+			}
+			let source = match fs::read_to_string(sidecar_path(&path, ".source.toml")).await {
+				Ok(source_toml) => toml::from_str(&source_toml)?,
+				Err(err) if err.kind() == IoErrorKind::NotFound => continue,
+				Err(err) => return Err(err.into()),
+			};
+			managed_jars.push(ManagedJar { path, source });
+		}
+	}
+	Ok(managed_jars)
+}
+
+pub fn sidecar_path(jar_path: &Path, suffix: &str) -> PathBuf {
+	let mut sidecar_path = jar_path.as_os_str().to_owned();
+	sidecar_path.push(suffix);
+	sidecar_path.into()
+}
+
+async fn remove_dir_if_exists(path: &Path) -> anyhow::Result<()> {
+	match fs::remove_dir_all(path).await {
+		Err(err) if err.kind() != IoErrorKind::NotFound => Err(err.into()),
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use axum::{
+		extract::State,
+		http::{header, HeaderMap},
+		routing::get,
+		Json, Router,
+	};
+	use serde_json::json;
+
+	use super::*;
+	use crate::{
+		lockfile::{LockfileMismatch, LOCKFILE_NAME},
+		resolvers::modrinth::ModrinthResolver,
+		schemas::{ModListItem, ModRepo, ModRepoChannel},
+		test_utils::{serve_mock, test_config},
+	};
+
+	/// The jar the mock Modrinth serves, and whether it lies about its hash
+	type MockJar = Arc<Mutex<(String, bool)>>;
+
+	async fn mock_modrinth(jar: MockJar) -> String {
+		let router = Router::new()
+			.route(
+				"/project/lithium",
+				get(|| async {
+					Json(
+						json!({ "id": "gvQqBUqZ", "title": "Lithium", "client_side": "optional", "server_side": "optional" }),
+					)
+				}),
+			)
+			.route(
+				"/project/gvQqBUqZ/version",
+				get(|State(jar): State<MockJar>, headers: HeaderMap| async move {
+					let (version, lie_about_hash) = jar.lock().unwrap().clone();
+					let jar_bytes = format!("lithium {version}");
+					let sha1 = if lie_about_hash {
+						"0".repeat(40)
+					} else {
+						hex::encode(Sha1::digest(&jar_bytes))
+					};
+					Json(json!([{
+						"id": version,
+						"version_number": version,
+						"version_type": "release",
+						"date_published": "2024-01-01T00:00:00Z",
+						"files": [{
+							"hashes": { "sha1": sha1, "sha512": hex::encode(Sha512::digest(&jar_bytes)) },
+							// The download URL has to point back at the mock
+							"url": format!("http://{}/download", headers[header::HOST].to_str().unwrap()),
+							"filename": format!("lithium-{version}.jar"),
+							"primary": true,
+							"size": jar_bytes.len(),
+						}],
+					}]))
+				}),
+			)
+			.route(
+				"/download",
+				get(|State(jar): State<MockJar>| async move { format!("lithium {}", jar.lock().unwrap().0) }),
+			)
+			.with_state(jar);
+		serve_mock(router).await
+	}
+
+	fn modpack() -> DrakermoreModConfig {
+		test_config(r#"mod_list = [{ id = "lithium", repo = "modrinth", channel = "release" }]"#)
+	}
+
+	#[tokio::test]
+	async fn sync_is_incremental_and_keeps_previous_mods_on_failure() {
+		let jar: MockJar = Arc::new(Mutex::new(("1.0".into(), false)));
+		let mod_resolver = ModResolver {
+			modrinth: ModrinthResolver::new(&mock_modrinth(jar.clone()).await).unwrap(),
+			curseforge: None,
+		};
+		let download_dir = tempfile::tempdir().unwrap();
+		let both_dir = download_dir.path().join("both");
+		let modpack = modpack();
+
+		let summary = sync_mods(&modpack, download_dir.path(), &mod_resolver).await.unwrap();
+		assert_eq!((summary.downloaded, summary.unchanged, summary.removed), (1, 0, 0));
+		assert_eq!(
+			std::fs::read_to_string(both_dir.join("lithium-1.0.jar")).unwrap(),
+			"lithium 1.0"
+		);
+		assert_eq!(
+			std::fs::read_to_string(both_dir.join("lithium-1.0.jar.name.txt")).unwrap(),
+			"Lithium\n"
+		);
+		assert!(!download_dir.path().join(STAGING_DIR_NAME).exists());
+
+		let summary = sync_mods(&modpack, download_dir.path(), &mod_resolver).await.unwrap();
+		assert_eq!((summary.downloaded, summary.unchanged, summary.removed), (0, 1, 0));
+
+		*jar.lock().unwrap() = ("2.0".into(), true);
+		assert!(sync_mods(&modpack, download_dir.path(), &mod_resolver).await.is_err());
+		assert!(both_dir.join("lithium-1.0.jar").exists());
+		assert!(!both_dir.join("lithium-2.0.jar").exists());
+
+		jar.lock().unwrap().1 = false;
+		let summary = sync_mods(&modpack, download_dir.path(), &mod_resolver).await.unwrap();
+		assert_eq!((summary.downloaded, summary.unchanged, summary.removed), (1, 0, 1));
+		assert!(!both_dir.join("lithium-1.0.jar").exists());
+		assert!(!both_dir.join("lithium-1.0.jar.source.toml").exists());
+		assert!(both_dir.join("lithium-2.0.jar.source.toml").exists());
+	}
+
+	/// A CurseForge mod which can't be downloaded from anywhere but CurseForge
+	fn minimap(jar_bytes: &[u8]) -> ResolvedMod {
+		ResolvedMod {
+			id: "xaeros-minimap".into(),
+			channel: ModRepoChannel::Release,
+			name: "Xaero's Minimap".into(),
+			version_name: "Xaero's Minimap 4000001".into(),
+			source: ModSource {
+				repo: ModRepo::Curseforge,
+				project_id: "263420".into(),
+				version_id: "4000001".into(),
+			},
+			file_name: "Xaeros_Minimap_4000001.jar".into(),
+			url: Some("https://edge.forgecdn.net/files/4000001/Xaeros_Minimap_4000001.jar".into()),
+			distribution_allowed: false,
+			sha1: hex::encode(Sha1::digest(jar_bytes)),
+			sha512: None,
+			size: jar_bytes.len() as u64,
+			side: PackwizModSide::Client,
+			supported_side: PackwizModSide::Client,
+			curseforge_fingerprint: None,
+			dependencies: Vec::new(),
+			required_by: Vec::new(),
+		}
+	}
+
+	#[tokio::test]
+	async fn undistributable_mods_need_a_manual_download() {
+		let jar_bytes = b"xaero's minimap";
+		let resolved_mod = minimap(jar_bytes);
+		let download_dir = tempfile::tempdir().unwrap();
+		let staging_dir = download_dir.path().join(STAGING_DIR_NAME);
+		std::fs::create_dir(&staging_dir).unwrap();
+		let mut modpack = modpack();
+
+		let err = stage_jars(
+			std::slice::from_ref(&resolved_mod),
+			0,
+			&modpack,
+			&[],
+			download_dir.path(),
+			&staging_dir,
+		)
+		.await
+		.unwrap_err();
+		assert!(err.to_string().contains("manual_downloads"), "{err}");
+
+		let manual_jar_path = download_dir.path().join("by-hand.jar");
+		std::fs::write(&manual_jar_path, jar_bytes).unwrap();
+		modpack.manual_downloads.insert("263420".into(), manual_jar_path);
+		let staged_jars = stage_jars(&[resolved_mod], 0, &modpack, &[], download_dir.path(), &staging_dir)
+			.await
+			.unwrap();
+		let staged_path = staged_jars[0].staged_path.as_ref().unwrap();
+		assert_eq!(std::fs::read(staged_path).unwrap(), jar_bytes);
+		assert_eq!(
+			staged_jars[0].target_path,
+			download_dir.path().join("client/Xaeros_Minimap_4000001.jar")
+		);
+	}
+
+	#[tokio::test]
+	async fn synced_mods_match_their_lockfile() {
+		let jar: MockJar = Arc::new(Mutex::new(("1.0".into(), false)));
+		let mod_resolver = ModResolver {
+			modrinth: ModrinthResolver::new(&mock_modrinth(jar).await).unwrap(),
+			curseforge: None,
+		};
+		let download_dir = tempfile::tempdir().unwrap();
+		let mut modpack = modpack();
+
+		let lockfile = sync_mods(&modpack, download_dir.path(), &mod_resolver)
+			.await
+			.unwrap()
+			.lockfile;
+		assert_eq!(lockfile.mods.len(), 1);
+		assert_eq!(lockfile.mods[0].sha512, hex::encode(Sha512::digest("lithium 1.0")));
+		let lockfile_path = download_dir.path().join(LOCKFILE_NAME);
+		lockfile.write_to_file(&lockfile_path).await.unwrap();
+		let lockfile = Lockfile::read_from_file(&lockfile_path).await.unwrap().unwrap();
+		assert_eq!(lockfile.verify(&modpack, download_dir.path()).await.unwrap(), []);
+
+		let jar_path = download_dir.path().join("both/lithium-1.0.jar");
+		std::fs::write(&jar_path, "lithium 1.1").unwrap();
+		modpack.mod_list.push(ModListItem {
+			id: "sodium".into(),
+			repo: ModRepo::Modrinth,
+			channel: ModRepoChannel::Release,
+		});
+		assert_eq!(
+			lockfile.verify(&modpack, download_dir.path()).await.unwrap(),
+			[
+				LockfileMismatch::Unlocked("sodium".into()),
+				LockfileMismatch::ChangedJar(jar_path)
+			]
+		);
+	}
+	#[tokio::test]
+	async fn downloads_stop_once_they_are_bigger_than_expected() {
+		let base_url = serve_mock(Router::new().route("/minimap.jar", get(|| async { "x".repeat(1 << 20) }))).await;
+		let mut resolved_mod = minimap(b"xaero's minimap");
+		resolved_mod.url = Some(format!("{base_url}/minimap.jar"));
+		resolved_mod.distribution_allowed = true;
+		let download_dir = tempfile::tempdir().unwrap();
+		let staging_dir = download_dir.path().join(STAGING_DIR_NAME);
+		std::fs::create_dir(&staging_dir).unwrap();
+
+		let err = stage_jars(&[resolved_mod], 0, &modpack(), &[], download_dir.path(), &staging_dir)
+			.await
+			.unwrap_err();
+		assert!(err.to_string().contains("sent more than that"), "{err}");
+		assert!(std::fs::metadata(staging_dir.join("0.jar")).unwrap().len() < 1 << 20);
+	}
+}
@@ -0,0 +1,64 @@
+//! Fixtures shared by the tests
+
+use std::{io::Write, path::Path};
+
+use axum::Router;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+	pack::Pack,
+	schemas::{DrakermoreModConfig, PackwizModSide},
+};
+
+/// The smallest config a pack can have
+const TEST_CONFIG: &str = r#"
+name = "Test pack"
+pack_author = "Tester"
+pack_version = "1.0.0"
+minecraft_version = "1.20.1"
+loader_version = "0.16.5"
+minecraft_servers = []
+"#;
+
+/// The test config, with the top-level keys in `overrides` added or replaced
+pub fn test_config_toml(overrides: &str) -> String {
+	let mut config: toml::Table = TEST_CONFIG.parse().unwrap();
+	config.extend(overrides.parse::<toml::Table>().unwrap());
+	config.to_string()
+}
+
+pub fn test_config(overrides: &str) -> DrakermoreModConfig {
+	toml::from_str(&test_config_toml(overrides)).unwrap()
+}
+
+/// Writes the test config and empty copy and download dirs to `root`, and returns a pack which uses them
+pub fn test_pack(root: &Path, config_overrides: &str) -> Pack {
+	std::fs::write(root.join("mod-list.toml"), test_config_toml(config_overrides)).unwrap();
+	std::fs::create_dir_all(root.join("copy")).unwrap();
+	for realm in PackwizModSide::all() {
+		std::fs::create_dir_all(root.join("download").join(realm.to_string())).unwrap();
+	}
+	Pack::new(
+		root.join("mod-list.toml"),
+		root.join("copy"),
+		root.join("download"),
+		"http://localhost".into(),
+	)
+	.unwrap()
+}
+
+/// Writes a jar which only has a fabric.mod.json in it
+pub fn write_jar(jar_path: &Path, fabric_mod_json: &str) {
+	let mut jar = ZipWriter::new(std::fs::File::create(jar_path).unwrap());
+	jar.start_file("fabric.mod.json", SimpleFileOptions::default()).unwrap();
+	jar.write_all(fabric_mod_json.as_bytes()).unwrap();
+	jar.finish().unwrap();
+}
+
+/// Serves `router` on a random local port until the test ends, and returns its base URL
+pub async fn serve_mock(router: Router) -> String {
+	let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let base_url = format!("http://{}", listener.local_addr().unwrap());
+	tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+	base_url
+}